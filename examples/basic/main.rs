@@ -106,8 +106,9 @@ async fn run_example(event_loop: EventLoop<()>, window: Window) {
     let vertex_shader = VertexShader::new(&device, "examples/basic/shader.vs").unwrap();
     let fragment_shader = FragmentShader::new(&device, "examples/basic/shader.fs").unwrap();
 
-    let render_node = RenderNode::builder()
+    let mut render_node = RenderNode::builder()
         .add_vertex_buffer::<Vertex>()
+        .set_index_format::<u16>()
         .set_vertex_shader(vertex_shader)
         .set_fragment_shader(fragment_shader)
         .add_local_uniform_bind_group(
@@ -136,6 +137,16 @@ async fn run_example(event_loop: EventLoop<()>, window: Window) {
         );
         match event {
             event::Event::MainEventsCleared => {
+                // Edit shader.vs or shader.fs while the example is running to reload them
+                match render_node.reload_shaders(&device) {
+                    Err(RenderError::ShaderCompileError { diagnostics, .. }) => {
+                        for diagnostic in diagnostics.iter() {
+                            eprintln!("{}", diagnostic.pretty_print(None));
+                        }
+                    }
+                    Err(err) => eprintln!("{}", err),
+                    Ok(_) => {}
+                }
                 window.request_redraw();
             }
             event::Event::WindowEvent { event, .. } => match event {
@@ -202,9 +213,9 @@ async fn run_example(event_loop: EventLoop<()>, window: Window) {
                     },
                 );
                 runner.set_vertex_buffer_data(0, &cube.vertices);
-                runner.set_index_buffer(cube.index_buf.slice(..));
+                runner.set_index_buffer_data(&cube.indices);
                 runner.set_texture_data(0, &cube.texture);
-                runner.draw_indexed(0..cube.index_count, 0, 0..1);
+                runner.draw_indexed(0..cube.indices.len(), 0, 0..1);
                 drop(runner);
                 queue.submit(vec![encoder.finish()]);
             }
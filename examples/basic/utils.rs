@@ -5,10 +5,13 @@ use smol_renderer::*;
 
 #[repr(C)]
 #[derive(GpuData)]
+#[layout(std140)]
 pub struct CameraGpuData {
     pub view_matrix: [[f32; 4]; 4],
     pub projection: [[f32; 4]; 4],
     pub view_pos: [f32; 3],
+    #[padding]
+    pub _padding: f32,
 }
 
 impl From<Camera> for CameraGpuData {
@@ -35,6 +38,7 @@ impl From<Camera> for CameraGpuData {
             ],
             projection: [projection[0], projection[1], projection[2], projection[3]],
             view_pos: [view_pos.x, view_pos.y, view_pos.z],
+            _padding: 0.0,
         }
     }
 }
@@ -60,6 +64,7 @@ impl From<ModelInfo> for RawModelInfo {
 }
 #[repr(C)]
 #[derive(GpuData)]
+#[layout(std140)]
 pub struct RawModelInfo {
     pub model_matrix: [[f32; 4]; 4],
 }
@@ -86,7 +91,7 @@ pub fn create_depth_texture(
 }
 
 #[repr(C)]
-#[derive(GpuData)]
+#[derive(GpuData, VertexBuffer)]
 pub struct Vertex {
     pos: [f32; 3],
     tex_coord: [f32; 2],
@@ -99,19 +104,10 @@ fn vertex(position: [i8; 3], tc: [i8; 2]) -> Vertex {
     }
 }
 
-impl VertexBuffer for Vertex {
-    const STEP_MODE: wgpu::InputStepMode = wgpu::InputStepMode::Vertex;
-
-    fn get_attributes<'a>() -> &'a [wgpu::VertexAttributeDescriptor] {
-        &wgpu::vertex_attr_array![0 => Float3, 1 => Float2]
-    }
-}
-
 pub struct Cube {
     pub vertices: ImmutableVertexData<Vertex>,
-    pub index_buf: wgpu::Buffer,
+    pub indices: ImmutableIndexData<u16>,
     pub texture: TextureData<SimpleTexture>,
-    pub index_count: u32,
 }
 
 pub fn create_cube(device: &wgpu::Device, queue: &wgpu::Queue) -> Cube {
@@ -148,8 +144,7 @@ pub fn create_cube(device: &wgpu::Device, queue: &wgpu::Queue) -> Cube {
         vertex([1, -1, -1], [0, 1]),
     ];
     let vertex_data = VertexBuffer::allocate_immutable_buffer(device, &vertex_data);
-    // index data format defaults to u32
-    let index_data: &[u32] = &[
+    let index_data: &[u16] = &[
         0, 1, 2, 2, 3, 0, // top
         4, 5, 6, 6, 7, 4, // bottom
         8, 9, 10, 10, 11, 8, // right
@@ -157,16 +152,12 @@ pub fn create_cube(device: &wgpu::Device, queue: &wgpu::Queue) -> Cube {
         16, 17, 18, 18, 19, 16, // front
         20, 21, 22, 22, 23, 20, // back
     ];
-    let index_count = index_data.len() as u32;
-    let index_data = unsafe {
-        std::slice::from_raw_parts(index_data.as_ptr() as *const u8, index_data.len() * 4)
-    };
+    let indices = IndexType::allocate_immutable_buffer(device, index_data);
     let texture =
         SimpleTexture::load_texture(&device, &queue, "examples/basic/cube-diffuse.png").unwrap();
     Cube {
         vertices: vertex_data,
-        index_buf: device.create_buffer_with_data(index_data, wgpu::BufferUsage::INDEX),
-        index_count,
+        indices,
         texture,
     }
 }
@@ -0,0 +1,94 @@
+use proc_macro2::TokenStream;
+use proc_macro_error::abort;
+use quote::quote;
+use proc_macro2::TokenTree;
+use std::path::{Path, PathBuf};
+use syn::{
+    parse::{Parse, ParseStream},
+    LitStr, Token,
+};
+
+pub struct IncludeShaderInput {
+    device: TokenStream,
+    path: LitStr,
+}
+
+impl Parse for IncludeShaderInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        // The device expression is kept as tokens up until the first top level
+        // comma since parsing arbitrary expressions requires the full syn feature
+        let mut device = TokenStream::new();
+        while !input.peek(Token![,]) {
+            device.extend(std::iter::once(input.parse::<TokenTree>()?));
+        }
+        input.parse::<Token![,]>()?;
+        let path = input.parse()?;
+        // Allow a trailing comma
+        let _ = input.parse::<Option<Token![,]>>()?;
+        Ok(IncludeShaderInput { device, path })
+    }
+}
+
+/// Embeds the SPIR-V compiled by compile_shader_dir in the build script and
+/// creates the shader type matching the extension of the path.
+pub fn expand(input: &IncludeShaderInput) -> TokenStream {
+    let path = input.path.value();
+    let shader_type = match path.rsplit('.').next() {
+        Some("vs") => quote! { VertexShader },
+        Some("fs") => quote! { FragmentShader },
+        Some("cs") => quote! { ComputeShader },
+        _ => abort! {
+            input.path,
+            format!("Can't infer the shader kind of {}", path);
+            help = "Use the .vs, .fs or .cs extension for vertex, fragment and compute shaders";
+        },
+    };
+    // OUT_DIR is set for the crate the macro is expanded in
+    let out_dir = match std::env::var_os("OUT_DIR") {
+        Some(out_dir) => PathBuf::from(out_dir),
+        None => abort! {
+            input.path,
+            "OUT_DIR isn't set";
+            help = "Compile the shaders with compile_shader_dir in the build script of the crate";
+        },
+    };
+    let spirv_path = spirv_path(&out_dir, &path);
+    let spirv_path = match spirv_path.to_str() {
+        Some(spirv_path) => LitStr::new(spirv_path, input.path.span()),
+        None => abort!(
+            input.path,
+            format!("{} isn't valid UTF-8", spirv_path.display())
+        ),
+    };
+    let device = &input.device;
+    quote! {
+        #shader_type::from_spirv_bytes(#device, include_bytes!(#spirv_path))
+    }
+}
+
+// Mirrors the output path of compile_shader_dir, the shader path always uses / as separator
+fn spirv_path(out_dir: &Path, path: &str) -> PathBuf {
+    let mut spirv_path = path
+        .split('/')
+        .filter(|component| !component.is_empty())
+        .fold(out_dir.join("shaders"), |dir, component| {
+            dir.join(component)
+        })
+        .into_os_string();
+    spirv_path.push(".spv");
+    PathBuf::from(spirv_path)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn spirv_paths() {
+        let out_dir = Path::new("out");
+        let expected = out_dir.join("shaders").join("post").join("blur.fs.spv");
+        assert_eq!(spirv_path(out_dir, "post/blur.fs"), expected);
+        assert_eq!(spirv_path(out_dir, "/post/blur.fs"), expected);
+    }
+}
@@ -0,0 +1,394 @@
+use crate::types::{self, array_len};
+use proc_macro_error::abort;
+use syn::{spanned::Spanned, Data, DeriveInput, Fields, Meta, NestedMeta, Type};
+
+#[derive(Clone, Copy)]
+enum Layout {
+    Std140,
+    Std430,
+}
+
+impl Layout {
+    fn name(self) -> &'static str {
+        match self {
+            Layout::Std140 => "std140",
+            Layout::Std430 => "std430",
+        }
+    }
+}
+
+// Size and alignment of a field both on the rust side (#[repr(C)])
+// and on the GLSL side for the chosen layout
+struct FieldLayout {
+    rust_size: usize,
+    rust_align: usize,
+    glsl_size: usize,
+    glsl_align: usize,
+}
+
+#[inline]
+fn round_up(value: usize, align: usize) -> usize {
+    value.div_ceil(align) * align
+}
+
+fn parse_layout(ast: &DeriveInput) -> Option<Layout> {
+    let attr = ast.attrs.iter().find(|attr| attr.path.is_ident("layout"))?;
+    if let Ok(Meta::List(list)) = attr.parse_meta() {
+        if let Some(NestedMeta::Meta(Meta::Path(path))) = list.nested.first() {
+            if list.nested.len() == 1 {
+                if path.is_ident("std140") {
+                    return Some(Layout::Std140);
+                } else if path.is_ident("std430") {
+                    return Some(Layout::Std430);
+                }
+            }
+        }
+    }
+    abort! {
+        attr,
+        "Invalid layout attribute";
+        help = "Use either #[layout(std140)] or #[layout(std430)]";
+    }
+}
+
+fn is_scalar(ty: &Type) -> bool {
+    if let Type::Path(path) = ty {
+        if let Some(ident) = path.path.get_ident() {
+            return ident == "f32" || ident == "u32" || ident == "i32";
+        }
+    }
+    false
+}
+
+fn unsupported_type(ty: &Type) -> ! {
+    types::unsupported_type(
+        ty,
+        "#[layout] GpuData field",
+        "Supported types are f32, u32, i32, vectors ([f32; 2-4]), \
+         matrices/arrays of vectors ([[f32; 2-4]; N]) and arrays of scalars",
+    )
+}
+
+// vecN of 4 byte scalars
+fn vector_layout(components: usize) -> (usize, usize) {
+    match components {
+        2 => (8, 8),
+        3 => (12, 16),
+        4 => (16, 16),
+        _ => unreachable!(),
+    }
+}
+
+fn array_stride_mismatch(ty: &Type, layout: Layout, rust_stride: usize, glsl_stride: usize) -> ! {
+    abort! {
+        ty,
+        format!(
+            "Array stride of {} bytes doesn't match the {} array stride of {} bytes",
+            rust_stride,
+            layout.name(),
+            glsl_stride
+        );
+        help = "Pad the elements to 16 bytes, e.g [[f32; 4]; N], and ignore the unused components";
+    }
+}
+
+fn field_layout(ty: &Type, layout: Layout) -> FieldLayout {
+    match ty {
+        _ if is_scalar(ty) => FieldLayout {
+            rust_size: 4,
+            rust_align: 4,
+            glsl_size: 4,
+            glsl_align: 4,
+        },
+        Type::Array(array) => {
+            let len = array_len(&array.len, "#[layout] GpuData");
+            if is_scalar(&array.elem) {
+                if (2..=4).contains(&len) {
+                    let (glsl_size, glsl_align) = vector_layout(len);
+                    return FieldLayout {
+                        rust_size: 4 * len,
+                        rust_align: 4,
+                        glsl_size,
+                        glsl_align,
+                    };
+                }
+                // Plain array of scalars
+                let glsl_stride = match layout {
+                    Layout::Std140 => 16,
+                    Layout::Std430 => 4,
+                };
+                if glsl_stride != 4 {
+                    array_stride_mismatch(ty, layout, 4, glsl_stride);
+                }
+                return FieldLayout {
+                    rust_size: 4 * len,
+                    rust_align: 4,
+                    glsl_size: 4 * len,
+                    glsl_align: glsl_stride,
+                };
+            }
+            // Matrices are laid out as arrays of column vectors
+            if let Type::Array(inner) = array.elem.as_ref() {
+                let components = array_len(&inner.len, "#[layout] GpuData");
+                if is_scalar(&inner.elem) && (2..=4).contains(&components) {
+                    let (vec_size, vec_align) = vector_layout(components);
+                    let glsl_align = match layout {
+                        Layout::Std140 => round_up(vec_align, 16),
+                        Layout::Std430 => vec_align,
+                    };
+                    let glsl_stride = round_up(vec_size, glsl_align);
+                    let rust_stride = 4 * components;
+                    if glsl_stride != rust_stride {
+                        array_stride_mismatch(ty, layout, rust_stride, glsl_stride);
+                    }
+                    return FieldLayout {
+                        rust_size: rust_stride * len,
+                        rust_align: 4,
+                        glsl_size: glsl_stride * len,
+                        glsl_align,
+                    };
+                }
+            }
+            unsupported_type(ty)
+        }
+        _ => unsupported_type(ty),
+    }
+}
+
+// Padding fields only need to be made out of 4 byte scalars
+// and aren't subject to the GLSL alignment rules
+fn padding_size(ty: &Type) -> usize {
+    match ty {
+        _ if is_scalar(ty) => 4,
+        Type::Array(array) if is_scalar(&array.elem) => {
+            4 * array_len(&array.len, "#[layout] GpuData")
+        }
+        _ => abort! {
+            ty,
+            "Padding fields must be f32, u32, i32 or arrays of them"
+        },
+    }
+}
+
+/// Verifies that the #[repr(C)] layout of the struct matches the GLSL layout
+/// given via #[layout(std140)] or #[layout(std430)], if any. This includes the
+/// trailing padding since the struct may be used as an array element.
+pub fn validate(ast: &DeriveInput) {
+    let layout = match parse_layout(ast) {
+        Some(layout) => layout,
+        None => return,
+    };
+    let name = &ast.ident;
+    let fields = match &ast.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            Fields::Unnamed(fields) => &fields.unnamed,
+            Fields::Unit => return,
+        },
+        _ => abort!(name, "#[layout] can only be used on structs"),
+    };
+
+    let mut rust_offset = 0;
+    let mut glsl_offset = 0;
+    let mut rust_struct_align = 4;
+    // std140 rounds the alignment of structs up to that of a vec4
+    let mut glsl_struct_align = match layout {
+        Layout::Std140 => 16,
+        Layout::Std430 => 4,
+    };
+    for field in fields {
+        if field.attrs.iter().any(|attr| attr.path.is_ident("padding")) {
+            let size = padding_size(&field.ty);
+            rust_offset = round_up(rust_offset, 4) + size;
+            glsl_offset = round_up(glsl_offset, 4) + size;
+            continue;
+        }
+        let field_layout = field_layout(&field.ty, layout);
+        rust_struct_align = rust_struct_align.max(field_layout.rust_align);
+        glsl_struct_align = glsl_struct_align.max(field_layout.glsl_align);
+        rust_offset = round_up(rust_offset, field_layout.rust_align);
+        glsl_offset = round_up(glsl_offset, field_layout.glsl_align);
+        if rust_offset != glsl_offset {
+            let field_name = field
+                .ident
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_else(|| "field".to_string());
+            abort! {
+                field.span(),
+                format!(
+                    "{} is placed at offset {} but {} requires offset {}",
+                    field_name,
+                    rust_offset,
+                    layout.name(),
+                    glsl_offset
+                );
+                help = if rust_offset < glsl_offset {
+                    format!(
+                        "Insert a #[padding] field of {} bytes before {}",
+                        glsl_offset - rust_offset,
+                        field_name
+                    )
+                } else {
+                    format!(
+                        "Remove {} bytes of #[padding] before {} or reorder the fields",
+                        rust_offset - glsl_offset,
+                        field_name
+                    )
+                };
+            }
+        }
+        rust_offset += field_layout.rust_size;
+        glsl_offset += field_layout.glsl_size;
+    }
+
+    let rust_size = round_up(rust_offset, rust_struct_align);
+    let glsl_size = round_up(glsl_offset, glsl_struct_align);
+    if rust_size != glsl_size {
+        abort! {
+            name,
+            format!(
+                "{} is {} bytes but {} requires a size of {} bytes",
+                name,
+                rust_size,
+                layout.name(),
+                glsl_size
+            );
+            help = if rust_size < glsl_size {
+                format!(
+                    "Insert a #[padding] field of {} bytes at the end of {}",
+                    glsl_size - rust_size,
+                    name
+                )
+            } else {
+                format!(
+                    "Remove {} bytes of #[padding] at the end of {} or reorder the fields",
+                    rust_size - glsl_size,
+                    name
+                )
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use syn::parse_quote;
+
+    // abort! panics outside of a proc macro invocation
+    fn is_valid(ast: &DeriveInput) -> bool {
+        catch_unwind(AssertUnwindSafe(|| validate(ast))).is_ok()
+    }
+
+    #[test]
+    fn std140() {
+        let ast: DeriveInput = parse_quote! {
+            #[repr(C)]
+            #[layout(std140)]
+            struct Camera {
+                view: [[f32; 4]; 4],
+                position: [f32; 3],
+                exposure: f32,
+            }
+        };
+        assert!(is_valid(&ast));
+        // The struct is rounded up to 16 bytes
+        let ast: DeriveInput = parse_quote! {
+            #[repr(C)]
+            #[layout(std140)]
+            struct Light {
+                position: [f32; 3],
+            }
+        };
+        assert!(!is_valid(&ast));
+        let ast: DeriveInput = parse_quote! {
+            #[repr(C)]
+            #[layout(std140)]
+            struct Light {
+                position: [f32; 3],
+                #[padding]
+                _padding: f32,
+            }
+        };
+        assert!(is_valid(&ast));
+        let ast: DeriveInput = parse_quote! {
+            #[repr(C)]
+            #[layout(std140)]
+            struct Scale {
+                scale: [f32; 2],
+            }
+        };
+        assert!(!is_valid(&ast));
+        // Vectors after a vec3 must be 16 byte aligned
+        let ast: DeriveInput = parse_quote! {
+            #[repr(C)]
+            #[layout(std140)]
+            struct Light {
+                position: [f32; 3],
+                color: [f32; 4],
+            }
+        };
+        assert!(!is_valid(&ast));
+        // Arrays of scalars have a 16 byte stride in std140
+        let ast: DeriveInput = parse_quote! {
+            #[repr(C)]
+            #[layout(std140)]
+            struct Weights {
+                weights: [f32; 8],
+            }
+        };
+        assert!(!is_valid(&ast));
+    }
+
+    #[test]
+    fn std430() {
+        let ast: DeriveInput = parse_quote! {
+            #[repr(C)]
+            #[layout(std430)]
+            struct Particle {
+                position: [f32; 2],
+                weights: [f32; 5],
+                mass: f32,
+            }
+        };
+        assert!(is_valid(&ast));
+        let ast: DeriveInput = parse_quote! {
+            #[repr(C)]
+            #[layout(std430)]
+            struct Scale {
+                scale: [f32; 2],
+            }
+        };
+        assert!(is_valid(&ast));
+        // The struct is rounded up to its largest alignment
+        let ast: DeriveInput = parse_quote! {
+            #[repr(C)]
+            #[layout(std430)]
+            struct Particle {
+                velocity: [f32; 2],
+                mass: f32,
+            }
+        };
+        assert!(!is_valid(&ast));
+        let ast: DeriveInput = parse_quote! {
+            #[repr(C)]
+            #[layout(std430)]
+            struct Particle {
+                position: [f32; 3],
+            }
+        };
+        assert!(!is_valid(&ast));
+        let ast: DeriveInput = parse_quote! {
+            #[repr(C)]
+            #[layout(std430)]
+            struct Particle {
+                position: [f32; 3],
+                mass: f32,
+            }
+        };
+        assert!(is_valid(&ast));
+    }
+}
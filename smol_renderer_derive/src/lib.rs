@@ -3,11 +3,12 @@ use proc_macro_error::{abort, proc_macro_error};
 use proc_macro::TokenStream;
 use quote::quote;
 
+mod include_shader;
+mod layout;
+mod types;
+mod vertex_buffer;
 
-#[proc_macro_error]
-#[proc_macro_derive(GpuData)]
-pub fn gpu_data_derive(input: TokenStream) -> TokenStream {
-    let ast: syn::DeriveInput = syn::parse(input).unwrap();
+fn assert_repr_c(ast: &syn::DeriveInput, trait_name: &str) {
     let name = &ast.ident;
     let stream: TokenStream  = quote! {#[repr(C)] struct Dummy;}.into();
     let repr_c_tokens: syn::DeriveInput = syn::parse(stream).unwrap();
@@ -15,14 +16,39 @@ pub fn gpu_data_derive(input: TokenStream) -> TokenStream {
         abort! {
             name,
             format!("Invalid ABI guarentee for {} struct", name); 
-            note = "All GpuData must #[repr(C)]"; 
-            help = "Add #[repr(C)] to your GpuData struct";
+            note = format!("All {} must #[repr(C)]", trait_name); 
+            help = format!("Add #[repr(C)] to your {} struct", trait_name);
         };
     }
+}
+
+#[proc_macro_error]
+#[proc_macro_derive(GpuData, attributes(layout, padding))]
+pub fn gpu_data_derive(input: TokenStream) -> TokenStream {
+    let ast: syn::DeriveInput = syn::parse(input).unwrap();
+    let name = &ast.ident;
+    assert_repr_c(&ast, "GpuData");
+    layout::validate(&ast);
     let gen = quote! {
         unsafe impl GpuData for #name {}
     };
     gen.into()
 }
 
+#[proc_macro_error]
+#[proc_macro_derive(VertexBuffer, attributes(location, step_mode, normalized))]
+pub fn vertex_buffer_derive(input: TokenStream) -> TokenStream {
+    let ast: syn::DeriveInput = syn::parse(input).unwrap();
+    assert_repr_c(&ast, "VertexBuffer");
+    vertex_buffer::expand(&ast).into()
+}
 
+/// Embeds a shader compiled to OUT_DIR by compile_shader_dir in a build script,
+/// the path is relative to the shader directory and the extension decides the
+/// shader type, e.g `include_shader!(&device, "shader.vs")` creates a VertexShader.
+#[proc_macro_error]
+#[proc_macro]
+pub fn include_shader(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as include_shader::IncludeShaderInput);
+    include_shader::expand(&input).into()
+}
@@ -0,0 +1,27 @@
+use proc_macro_error::abort;
+use syn::{Expr, Lit, Type};
+
+// Only integer literals can be evaluated in a derive, `what` names the
+// kind of field in the error
+pub fn array_len(len: &Expr, what: &str) -> usize {
+    if let Expr::Lit(expr_lit) = len {
+        if let Lit::Int(int) = &expr_lit.lit {
+            if let Ok(len) = int.base10_parse::<usize>() {
+                return len;
+            }
+        }
+    }
+    abort! {
+        len,
+        format!("{} array lengths must be integer literals", what);
+        help = "Replace the length with an integer literal";
+    }
+}
+
+pub fn unsupported_type(ty: &Type, what: &str, supported: &str) -> ! {
+    abort! {
+        ty,
+        format!("Unsupported {} type", what);
+        note = supported;
+    }
+}
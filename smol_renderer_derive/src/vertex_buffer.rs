@@ -0,0 +1,368 @@
+use crate::types::{self, array_len};
+use proc_macro2::TokenStream;
+use proc_macro_error::abort;
+use quote::quote;
+use syn::{spanned::Spanned, Data, DeriveInput, Fields, Lit, Meta, NestedMeta, Type};
+
+struct AttributeFormat {
+    format: &'static str,
+    size: usize,
+    align: usize,
+}
+
+fn scalar_info(ty: &Type) -> Option<(&'static str, usize)> {
+    if let Type::Path(path) = ty {
+        let ident = path.path.get_ident()?;
+        let info = match ident.to_string().as_str() {
+            "f32" => ("f32", 4),
+            "u32" => ("u32", 4),
+            "i32" => ("i32", 4),
+            "u16" => ("u16", 2),
+            "i16" => ("i16", 2),
+            "u8" => ("u8", 1),
+            "i8" => ("i8", 1),
+            _ => return None,
+        };
+        return Some(info);
+    }
+    None
+}
+
+fn vertex_format(scalar: &str, components: usize, normalized: bool) -> Option<&'static str> {
+    let format = match (scalar, components, normalized) {
+        ("f32", 1, false) => "Float",
+        ("f32", 2, false) => "Float2",
+        ("f32", 3, false) => "Float3",
+        ("f32", 4, false) => "Float4",
+        ("u32", 1, false) => "Uint",
+        ("u32", 2, false) => "Uint2",
+        ("u32", 3, false) => "Uint3",
+        ("u32", 4, false) => "Uint4",
+        ("i32", 1, false) => "Int",
+        ("i32", 2, false) => "Int2",
+        ("i32", 3, false) => "Int3",
+        ("i32", 4, false) => "Int4",
+        ("u16", 2, false) => "Ushort2",
+        ("u16", 4, false) => "Ushort4",
+        ("u16", 2, true) => "Ushort2Norm",
+        ("u16", 4, true) => "Ushort4Norm",
+        ("i16", 2, false) => "Short2",
+        ("i16", 4, false) => "Short4",
+        ("i16", 2, true) => "Short2Norm",
+        ("i16", 4, true) => "Short4Norm",
+        ("u8", 2, false) => "Uchar2",
+        ("u8", 4, false) => "Uchar4",
+        ("u8", 2, true) => "Uchar2Norm",
+        ("u8", 4, true) => "Uchar4Norm",
+        ("i8", 2, false) => "Char2",
+        ("i8", 4, false) => "Char4",
+        ("i8", 2, true) => "Char2Norm",
+        ("i8", 4, true) => "Char4Norm",
+        _ => return None,
+    };
+    Some(format)
+}
+
+fn unsupported_type(ty: &Type) -> ! {
+    types::unsupported_type(
+        ty,
+        "vertex attribute",
+        "Supported types are f32, u32, i32, arrays of them with 2-4 components, \
+         [u8; 2|4], [i8; 2|4], [u16; 2|4], [i16; 2|4] and [[f32; N]; M] matrices",
+    )
+}
+
+// Returns one format per shader location the field occupies,
+// matrices ([[f32; N]; M]) take up M consecutive locations.
+fn field_formats(ty: &Type, normalized: bool) -> Vec<AttributeFormat> {
+    let make = |scalar: &str, size: usize, components: usize| {
+        let format =
+            vertex_format(scalar, components, normalized).unwrap_or_else(|| unsupported_type(ty));
+        AttributeFormat {
+            format,
+            size: size * components,
+            align: size,
+        }
+    };
+    match ty {
+        Type::Array(array) => {
+            let len = array_len(&array.len, "Vertex attribute");
+            if let Some((scalar, size)) = scalar_info(&array.elem) {
+                vec![make(scalar, size, len)]
+            } else if let Type::Array(inner) = array.elem.as_ref() {
+                let inner_len = array_len(&inner.len, "Vertex attribute");
+                match scalar_info(&inner.elem) {
+                    Some(("f32", size)) if !normalized && (1..=4).contains(&len) => {
+                        (0..len).map(|_| make("f32", size, inner_len)).collect()
+                    }
+                    _ => unsupported_type(ty),
+                }
+            } else {
+                unsupported_type(ty)
+            }
+        }
+        _ => match scalar_info(ty) {
+            Some((scalar, size)) => vec![make(scalar, size, 1)],
+            None => unsupported_type(ty),
+        },
+    }
+}
+
+fn parse_location(attr: &syn::Attribute) -> u32 {
+    if let Ok(Meta::List(list)) = attr.parse_meta() {
+        if list.nested.len() == 1 {
+            if let Some(NestedMeta::Lit(Lit::Int(int))) = list.nested.first() {
+                if let Ok(location) = int.base10_parse::<u32>() {
+                    return location;
+                }
+            }
+        }
+    }
+    abort! {
+        attr,
+        "Invalid location attribute";
+        help = "The location must be specified as #[location(n)]";
+    }
+}
+
+fn parse_step_mode(ast: &DeriveInput) -> TokenStream {
+    let attr = match ast.attrs.iter().find(|attr| attr.path.is_ident("step_mode")) {
+        Some(attr) => attr,
+        None => return quote! { wgpu::InputStepMode::Vertex },
+    };
+    if let Ok(Meta::List(list)) = attr.parse_meta() {
+        if let Some(NestedMeta::Meta(Meta::Path(path))) = list.nested.first() {
+            if list.nested.len() == 1 {
+                if path.is_ident("vertex") {
+                    return quote! { wgpu::InputStepMode::Vertex };
+                } else if path.is_ident("instance") {
+                    return quote! { wgpu::InputStepMode::Instance };
+                }
+            }
+        }
+    }
+    abort! {
+        attr,
+        "Invalid step_mode attribute";
+        help = "Use either #[step_mode(vertex)] or #[step_mode(instance)]";
+    }
+}
+
+pub fn expand(ast: &DeriveInput) -> TokenStream {
+    let name = &ast.ident;
+    let fields = match &ast.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            Fields::Unnamed(fields) => &fields.unnamed,
+            Fields::Unit => abort!(name, "VertexBuffer can't be derived for unit structs"),
+        },
+        _ => abort!(name, "VertexBuffer can only be derived for structs"),
+    };
+    let step_mode = parse_step_mode(ast);
+
+    let mut offset = 0;
+    let mut next_location = 0;
+    let mut used_locations: Vec<u32> = Vec::new();
+    let mut attributes = Vec::new();
+    for field in fields {
+        let normalized = field
+            .attrs
+            .iter()
+            .any(|attr| attr.path.is_ident("normalized"));
+        if let Some(attr) = field
+            .attrs
+            .iter()
+            .find(|attr| attr.path.is_ident("location"))
+        {
+            next_location = parse_location(attr);
+        }
+        for attribute_format in field_formats(&field.ty, normalized) {
+            if used_locations.contains(&next_location) {
+                abort! {
+                    field.span(),
+                    format!("Shader location {} is already used by another field", next_location);
+                    help = "Change the #[location(n)] attribute so the locations don't overlap";
+                }
+            }
+            // Mirrors the #[repr(C)] layout rules
+            let misalignment = offset % attribute_format.align;
+            if misalignment != 0 {
+                offset += attribute_format.align - misalignment;
+            }
+            let format = syn::Ident::new(attribute_format.format, field.ty.span());
+            let location = next_location;
+            let attribute_offset = offset as u64;
+            attributes.push(quote! {
+                wgpu::VertexAttributeDescriptor {
+                    offset: #attribute_offset,
+                    format: wgpu::VertexFormat::#format,
+                    shader_location: #location,
+                }
+            });
+            used_locations.push(next_location);
+            next_location += 1;
+            offset += attribute_format.size;
+        }
+    }
+
+    quote! {
+        impl VertexBuffer for #name {
+            const STEP_MODE: wgpu::InputStepMode = #step_mode;
+
+            fn get_attributes<'a>() -> &'a [wgpu::VertexAttributeDescriptor] {
+                const ATTRIBUTES: &[wgpu::VertexAttributeDescriptor] = &[#(#attributes),*];
+                ATTRIBUTES
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use syn::parse_quote;
+
+    // abort! panics outside of a proc macro invocation
+    fn is_rejected(ast: &DeriveInput) -> bool {
+        catch_unwind(AssertUnwindSafe(|| expand(ast))).is_err()
+    }
+
+    #[test]
+    fn attributes() {
+        let ast: DeriveInput = parse_quote! {
+            #[repr(C)]
+            #[step_mode(instance)]
+            struct Instance {
+                #[location(2)]
+                model: [[f32; 4]; 4],
+                #[normalized]
+                color: [u8; 4],
+                #[location(8)]
+                id: u32,
+                scale: f32,
+            }
+        };
+        let expected = quote! {
+            impl VertexBuffer for Instance {
+                const STEP_MODE: wgpu::InputStepMode = wgpu::InputStepMode::Instance;
+
+                fn get_attributes<'a>() -> &'a [wgpu::VertexAttributeDescriptor] {
+                    const ATTRIBUTES: &[wgpu::VertexAttributeDescriptor] = &[
+                        wgpu::VertexAttributeDescriptor {
+                            offset: 0u64,
+                            format: wgpu::VertexFormat::Float4,
+                            shader_location: 2u32,
+                        },
+                        wgpu::VertexAttributeDescriptor {
+                            offset: 16u64,
+                            format: wgpu::VertexFormat::Float4,
+                            shader_location: 3u32,
+                        },
+                        wgpu::VertexAttributeDescriptor {
+                            offset: 32u64,
+                            format: wgpu::VertexFormat::Float4,
+                            shader_location: 4u32,
+                        },
+                        wgpu::VertexAttributeDescriptor {
+                            offset: 48u64,
+                            format: wgpu::VertexFormat::Float4,
+                            shader_location: 5u32,
+                        },
+                        wgpu::VertexAttributeDescriptor {
+                            offset: 64u64,
+                            format: wgpu::VertexFormat::Uchar4Norm,
+                            shader_location: 6u32,
+                        },
+                        wgpu::VertexAttributeDescriptor {
+                            offset: 68u64,
+                            format: wgpu::VertexFormat::Uint,
+                            shader_location: 8u32,
+                        },
+                        wgpu::VertexAttributeDescriptor {
+                            offset: 72u64,
+                            format: wgpu::VertexFormat::Float,
+                            shader_location: 9u32,
+                        }
+                    ];
+                    ATTRIBUTES
+                }
+            }
+        };
+        assert_eq!(expand(&ast).to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn step_mode() {
+        let ast: DeriveInput = parse_quote! {
+            #[repr(C)]
+            struct Vertex {
+                position: [f32; 3],
+            }
+        };
+        assert_eq!(
+            parse_step_mode(&ast).to_string(),
+            quote! { wgpu::InputStepMode::Vertex }.to_string()
+        );
+        let ast: DeriveInput = parse_quote! {
+            #[repr(C)]
+            #[step_mode(vertex)]
+            struct Vertex {
+                position: [f32; 3],
+            }
+        };
+        assert_eq!(
+            parse_step_mode(&ast).to_string(),
+            quote! { wgpu::InputStepMode::Vertex }.to_string()
+        );
+        let ast: DeriveInput = parse_quote! {
+            #[repr(C)]
+            #[step_mode(per_frame)]
+            struct Vertex {
+                position: [f32; 3],
+            }
+        };
+        assert!(is_rejected(&ast));
+    }
+
+    #[test]
+    fn invalid_attributes() {
+        // Overlapping locations
+        let ast: DeriveInput = parse_quote! {
+            #[repr(C)]
+            struct Vertex {
+                #[location(1)]
+                model: [[f32; 4]; 2],
+                #[location(2)]
+                color: [f32; 4],
+            }
+        };
+        assert!(is_rejected(&ast));
+        let ast: DeriveInput = parse_quote! {
+            #[repr(C)]
+            struct Vertex {
+                #[location(first)]
+                position: [f32; 3],
+            }
+        };
+        assert!(is_rejected(&ast));
+        // Only 8 and 16 bit integer vectors can be normalized
+        let ast: DeriveInput = parse_quote! {
+            #[repr(C)]
+            struct Vertex {
+                #[normalized]
+                position: [f32; 3],
+            }
+        };
+        assert!(is_rejected(&ast));
+        let ast: DeriveInput = parse_quote! {
+            #[repr(C)]
+            struct Vertex {
+                #[normalized]
+                color: [u8; 3],
+            }
+        };
+        assert!(is_rejected(&ast));
+    }
+}
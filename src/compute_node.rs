@@ -0,0 +1,252 @@
+use crate::{
+    reflection,
+    render_node::collect_bind_group_entries,
+    shader::ComputeShader,
+    textures::{TextureData, TextureShaderLayout},
+    uniforms::UniformBindGroup,
+    GpuData, RenderError,
+};
+use std::{
+    any::{type_name, TypeId},
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+
+pub struct ComputeNode {
+    shared_uniform_bind_groups: Vec<Arc<UniformBindGroup>>,
+    local_uniform_bind_groups: Vec<UniformBindGroup>,
+    texture_types: Vec<TypeId>,
+    workgroup_size: [u32; 3],
+    pipeline: wgpu::ComputePipeline,
+}
+
+pub struct ComputeNodeRunner<'a, 'b: 'a> {
+    compute_pass: wgpu::ComputePass<'a>,
+    texture_types: &'b Vec<TypeId>,
+    shared_uniform_bind_groups: &'b [Arc<UniformBindGroup>],
+    local_uniform_bind_groups: &'b [UniformBindGroup],
+    workgroup_size: [u32; 3],
+}
+
+impl<'a, 'b: 'a> ComputeNodeRunner<'a, 'b> {
+    #[inline]
+    pub fn set_texture_data<T: TextureShaderLayout>(
+        &mut self,
+        index: u32,
+        data: &'b TextureData<T>,
+    ) {
+        assert!(
+            TypeId::of::<T>() == self.texture_types[index as usize],
+            format!(
+                "{}, doesn't match the Texture type on index {}",
+                type_name::<T>(),
+                index
+            )
+        );
+        self.compute_pass
+            .set_bind_group(index, &data.bind_group, &[]);
+    }
+
+    /// Dispatches enough workgroups to cover the given number of invocations
+    /// based on the workgroup size declared in the compute shader.
+    #[inline]
+    pub fn dispatch_invocations(&mut self, x: u32, y: u32, z: u32) {
+        // Rounds up without overflowing for invocation counts close to u32::MAX
+        let workgroups = |count: u32, size: u32| count / size + (count % size != 0) as u32;
+        let [size_x, size_y, size_z] = self.workgroup_size;
+        self.compute_pass.dispatch(
+            workgroups(x, size_x),
+            workgroups(y, size_y),
+            workgroups(z, size_z),
+        );
+    }
+
+    /// Selects which slot of every dynamic binding in the uniform bind group should be used,
+    /// `uniform_index` counts the shared bind groups first followed by the local ones.
+    #[inline]
+    pub fn set_dynamic_slots(
+        &mut self,
+        uniform_index: usize,
+        slots: &[u32],
+    ) -> Result<(), RenderError> {
+        let group = UniformBindGroup::find_dynamic_group(
+            self.shared_uniform_bind_groups,
+            self.local_uniform_bind_groups,
+            uniform_index,
+        )?;
+        let offsets = group.get_dynamic_offsets(slots)?;
+        self.compute_pass.set_bind_group(
+            (self.texture_types.len() + uniform_index) as u32,
+            group.get_bind_group(),
+            &offsets,
+        );
+        Ok(())
+    }
+}
+
+impl<'a> Deref for ComputeNodeRunner<'a, '_> {
+    type Target = wgpu::ComputePass<'a>;
+    fn deref(&self) -> &Self::Target {
+        &self.compute_pass
+    }
+}
+
+impl<'a> DerefMut for ComputeNodeRunner<'a, '_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.compute_pass
+    }
+}
+
+#[derive(Default)]
+pub struct ComputeNodeBuilder {
+    local_uniform_bind_groups: Vec<UniformBindGroup>,
+    shared_uniform_bind_groups: Vec<Arc<UniformBindGroup>>,
+    compute_shader: Option<ComputeShader>,
+    texture_types: Vec<TypeId>,
+    texture_layout_entries: Vec<&'static [wgpu::BindGroupLayoutEntry]>,
+    texture_layout_generators: Vec<Box<dyn Fn(&wgpu::Device) -> &'static wgpu::BindGroupLayout>>,
+}
+
+impl ComputeNodeBuilder {
+    pub fn add_local_uniform_bind_group(mut self, uniform: UniformBindGroup) -> Self {
+        self.local_uniform_bind_groups.push(uniform);
+        self
+    }
+
+    pub fn add_shared_uniform_bind_group(mut self, shared_uniform: Arc<UniformBindGroup>) -> Self {
+        self.shared_uniform_bind_groups.push(shared_uniform);
+        self
+    }
+
+    pub fn add_texture<T: TextureShaderLayout>(mut self) -> Self {
+        self.texture_types.push(TypeId::of::<T>());
+        self.texture_layout_entries.push(T::get_layout_entries());
+        self.texture_layout_generators
+            .push(Box::new(move |device: &wgpu::Device| T::get_layout(device)));
+        self
+    }
+
+    pub fn set_compute_shader(mut self, compute_shader: ComputeShader) -> Self {
+        self.compute_shader = Some(compute_shader);
+        self
+    }
+
+    fn validate_shader(&self, compute_shader: &ComputeShader) -> Result<(), RenderError> {
+        let bind_group_entries = collect_bind_group_entries(
+            &self.texture_layout_entries,
+            &self.shared_uniform_bind_groups,
+            &self.local_uniform_bind_groups,
+        );
+        reflection::validate_bindings(
+            compute_shader.get_entry_point_reflection()?,
+            wgpu::ShaderStage::COMPUTE,
+            &bind_group_entries,
+        )
+    }
+
+    fn construct_pipeline(
+        &self,
+        device: &wgpu::Device,
+        compute_shader: &ComputeShader,
+    ) -> wgpu::ComputePipeline {
+        let texture_layouts = self
+            .texture_layout_generators
+            .iter()
+            .map(|gen| gen(&device));
+
+        let local_bind_group_layouts = self
+            .local_uniform_bind_groups
+            .iter()
+            .map(UniformBindGroup::get_layout);
+
+        let shared_bind_group_layouts = self
+            .shared_uniform_bind_groups
+            .iter()
+            .map(|group| group.get_layout());
+
+        let bind_group_layouts = texture_layouts
+            .chain(shared_bind_group_layouts)
+            .chain(local_bind_group_layouts)
+            .collect::<Vec<&wgpu::BindGroupLayout>>();
+
+        let compute_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                bind_group_layouts: &bind_group_layouts,
+            });
+
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            layout: &compute_pipeline_layout,
+            compute_stage: compute_shader.get_descriptor(),
+        })
+    }
+
+    pub fn build(self, device: &wgpu::Device) -> Result<ComputeNode, RenderError> {
+        let compute_shader = self
+            .compute_shader
+            .as_ref()
+            .ok_or(RenderError::MissingComputeShader)?;
+        self.validate_shader(compute_shader)?;
+        let pipeline = self.construct_pipeline(device, compute_shader);
+        let workgroup_size = compute_shader
+            .get_entry_point_reflection()?
+            .workgroup_size
+            .unwrap_or([1, 1, 1]);
+        Ok(ComputeNode {
+            shared_uniform_bind_groups: self.shared_uniform_bind_groups,
+            local_uniform_bind_groups: self.local_uniform_bind_groups,
+            texture_types: self.texture_types,
+            workgroup_size,
+            pipeline,
+        })
+    }
+}
+
+impl ComputeNode {
+    pub fn builder() -> ComputeNodeBuilder {
+        ComputeNodeBuilder::default()
+    }
+
+    #[inline]
+    pub fn update(
+        &self,
+        device: &wgpu::Device,
+        command_encoder: &mut wgpu::CommandEncoder,
+        bind_group_index: usize,
+        data: &impl GpuData,
+    ) -> Result<(), RenderError> {
+        self.local_uniform_bind_groups[bind_group_index].update_buffer_data(
+            device,
+            command_encoder,
+            data,
+        )
+    }
+
+    pub fn runner<'a: 'b, 'b>(
+        &'a self,
+        command_encoder: &'b mut wgpu::CommandEncoder,
+    ) -> ComputeNodeRunner<'a, 'b> {
+        let mut compute_pass = command_encoder.begin_compute_pass();
+        compute_pass.set_pipeline(&self.pipeline);
+        let local_iter = self.local_uniform_bind_groups.iter();
+        self.shared_uniform_bind_groups
+            .iter()
+            .map(|shared| shared.deref())
+            .chain(local_iter)
+            .enumerate()
+            .for_each(|(i, group)| {
+                compute_pass.set_bind_group(
+                    (self.texture_types.len() + i) as u32,
+                    group.get_bind_group(),
+                    &group.get_default_dynamic_offsets(),
+                );
+            });
+
+        ComputeNodeRunner {
+            compute_pass,
+            texture_types: &self.texture_types,
+            shared_uniform_bind_groups: &self.shared_uniform_bind_groups,
+            local_uniform_bind_groups: &self.local_uniform_bind_groups,
+            workgroup_size: self.workgroup_size,
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use crate::RenderError;
+use std::{marker::PhantomData, ops::RangeBounds};
+use wgpu::BufferAddress;
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
+}
+
+/// Implemented for the index types supported by wgpu, u16 and u32.
+pub trait IndexType: private::Sealed + Copy + 'static {
+    const FORMAT: wgpu::IndexFormat;
+
+    fn allocate_immutable_buffer(
+        device: &wgpu::Device,
+        indices: &[Self],
+    ) -> ImmutableIndexData<Self> {
+        ImmutableIndexData {
+            buffer: device.create_buffer_with_data(&index_bytes(indices), wgpu::BufferUsage::INDEX),
+            len: indices.len() as u32,
+            _marker: PhantomData::default(),
+        }
+    }
+
+    fn allocate_mutable_buffer(device: &wgpu::Device, indices: &[Self]) -> MutableIndexData<Self> {
+        MutableIndexData {
+            buffer: device.create_buffer_with_data(
+                &index_bytes(indices),
+                wgpu::BufferUsage::INDEX | wgpu::BufferUsage::COPY_DST,
+            ),
+            len: indices.len() as u32,
+            capacity: indices.len(),
+            _marker: PhantomData::default(),
+        }
+    }
+}
+
+impl IndexType for u16 {
+    const FORMAT: wgpu::IndexFormat = wgpu::IndexFormat::Uint16;
+}
+
+impl IndexType for u32 {
+    const FORMAT: wgpu::IndexFormat = wgpu::IndexFormat::Uint32;
+}
+
+// Buffer copies needs to be 4 byte aligned so odd numbers
+// of u16 indices are padded with an extra zero index
+fn index_bytes<I: IndexType>(indices: &[I]) -> Vec<u8> {
+    let raw_bytes = unsafe {
+        std::slice::from_raw_parts(
+            indices.as_ptr() as *const u8,
+            indices.len() * std::mem::size_of::<I>(),
+        )
+    };
+    let mut bytes = raw_bytes.to_vec();
+    bytes.resize((bytes.len() + 3) / 4 * 4, 0);
+    bytes
+}
+
+pub trait IndexBufferData {
+    type IndexType: IndexType;
+    fn get_gpu_buffer(&self) -> &wgpu::Buffer;
+    fn slice<S: RangeBounds<BufferAddress>>(&self, bounds: S) -> wgpu::BufferSlice;
+    /// Number of indices in the buffer
+    fn len(&self) -> u32;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+pub struct ImmutableIndexData<I: IndexType> {
+    pub(crate) buffer: wgpu::Buffer,
+    len: u32,
+    _marker: PhantomData<I>,
+}
+
+pub struct MutableIndexData<I: IndexType> {
+    pub(crate) buffer: wgpu::Buffer,
+    len: u32,
+    capacity: usize,
+    _marker: PhantomData<I>,
+}
+
+impl<I: IndexType> IndexBufferData for ImmutableIndexData<I> {
+    type IndexType = I;
+
+    fn get_gpu_buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    fn slice<S: RangeBounds<BufferAddress>>(&self, bounds: S) -> wgpu::BufferSlice {
+        self.buffer.slice(bounds)
+    }
+
+    fn len(&self) -> u32 {
+        self.len
+    }
+}
+
+impl<I: IndexType> IndexBufferData for MutableIndexData<I> {
+    type IndexType = I;
+
+    fn get_gpu_buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    fn slice<S: RangeBounds<BufferAddress>>(&self, bounds: S) -> wgpu::BufferSlice {
+        self.buffer.slice(bounds)
+    }
+
+    fn len(&self) -> u32 {
+        self.len
+    }
+}
+
+impl<I: IndexType> MutableIndexData<I> {
+    /// Replaces the indices in the buffer, the number of indices can't exceed
+    /// the amount the buffer was allocated with.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        indices: &[I],
+    ) -> Result<(), RenderError> {
+        if indices.len() > self.capacity {
+            return Err(RenderError::BufferCapacityExceeded {
+                capacity: self.capacity,
+                required: indices.len(),
+            });
+        }
+        self.len = indices.len() as u32;
+        let raw_bytes = index_bytes(indices);
+        let staging_buffer =
+            device.create_buffer_with_data(&raw_bytes, wgpu::BufferUsage::COPY_SRC);
+        encoder.copy_buffer_to_buffer(&staging_buffer, 0, &self.buffer, 0, raw_bytes.len() as u64);
+        Ok(())
+    }
+}
@@ -1,17 +1,50 @@
+pub mod compute_node;
+pub mod index_buffer;
+mod reflection;
+pub mod render_graph;
 pub mod render_node;
 pub mod shader;
+#[cfg(feature = "shaderc")]
+pub mod shader_build;
+#[cfg(feature = "shaderc")]
+pub mod shader_cache;
+pub mod shader_config;
+pub mod shader_diagnostics;
+#[cfg(feature = "shaderc")]
+pub mod shader_variants;
+#[cfg(test)]
+mod test_utils;
 pub mod textures;
 pub mod uniforms;
 pub mod vertex_buffer;
 
+use shader_diagnostics::format_diagnostics;
 use std::path::PathBuf;
 use thiserror::Error;
 
+pub use compute_node::{ComputeNode, ComputeNodeBuilder, ComputeNodeRunner};
+pub use index_buffer::{ImmutableIndexData, IndexBufferData, IndexType, MutableIndexData};
+pub use render_graph::{
+    AttachmentDescriptor, AttachmentSize, GraphPass, PassContext, RenderGraph, RenderGraphBuilder,
+};
 pub use render_node::{RenderNode, RenderNodeBuilder, RenderNodeRunner};
-pub use shader::{FragmentShader, VertexShader};
+pub use shader::{
+    ComputeShader, FragmentShader, ShaderCompileOptions, ShaderLanguage, VertexShader,
+};
+pub use shader_config::{ShaderCompileConfig, ShaderOptimizationLevel, ShaderTargetEnv};
+pub use shader_diagnostics::{DiagnosticSeverity, ShaderDiagnostic};
+#[cfg(feature = "shaderc")]
+pub use shader_variants::{ShaderDefines, ShaderVariantKind, ShaderVariants};
 pub use smol_renderer_derive::*;
 pub use textures::{
-    simpletexture::SimpleTexture, LoadableTexture, Texture, TextureData, TextureShaderLayout,
+    compressed::CompressedTexture,
+    cubemap::CubeMapTexture,
+    mipmaps::{MipmapFilter, MipmapGeneration, MipmapGenerator},
+    pixel_format::ColorSpace,
+    simpletexture::SimpleTexture,
+    texturearray::TextureArray,
+    volumetexture::VolumeTexture,
+    LoadableTexture, Texture, TextureData, TextureLoadOptions, TextureShaderLayout,
 };
 pub use uniforms::{UniformBindGroup, UniformBindGroupBuilder};
 pub use vertex_buffer::{ImmutableVertexData, MutableVertexData, VertexBuffer, VertexBufferData};
@@ -28,18 +61,39 @@ pub unsafe trait GpuData: 'static + Sized {
 
 #[derive(Error, Debug)]
 pub enum RenderError {
-    #[error("Couldn't compile shader file {path:?}: {compile_error:?}")]
+    #[error("Couldn't compile shader file {path:?}:\n{}", format_diagnostics(.diagnostics))]
     ShaderCompileError {
-        compile_error: String,
+        diagnostics: Vec<ShaderDiagnostic>,
         path: PathBuf,
     },
 
     #[error("You must set a VertexShader")]
     MissingVertexShader,
 
+    #[error("You must set a ComputeShader")]
+    MissingComputeShader,
+
     #[error("Couldn't open image")]
     TextureLoadError(#[from] image::ImageError),
 
+    #[error("Couldn't load compressed texture: {reason}")]
+    CompressedTextureError { reason: String },
+
+    #[error("The texture format {format:?} requires the {feature:?} feature")]
+    MissingTextureFeature {
+        format: wgpu::TextureFormat,
+        feature: wgpu::Features,
+    },
+
+    #[error("Texture layer {layer} doesn't match the first layer: {reason}")]
+    TextureLayerMismatch { layer: usize, reason: String },
+
+    #[error("Invalid texture layer stack: {reason}")]
+    InvalidLayerStack { reason: String },
+
+    #[error("Couldn't load cube map: {reason}")]
+    CubeMapLayoutError { reason: String },
+
     #[error("Issue with opening file")]
     FileReadError(#[from] std::io::Error),
 
@@ -51,4 +105,70 @@ pub enum RenderError {
 
     #[error("There doesn't exist a binding for this GpuData in this bindgroup")]
     GpuDataTypeNotPresent,
+
+    #[error("The binding for this GpuData isn't a dynamic binding")]
+    GpuDataBindingNotDynamic,
+
+    #[error("The buffer can only hold {capacity} elements but {required} are needed")]
+    BufferCapacityExceeded { capacity: usize, required: usize },
+
+    #[error("Invalid dynamic uniform slots: {reason}")]
+    InvalidDynamicSlots { reason: String },
+
+    #[error("Storage array bindings need room for at least one element")]
+    EmptyStorageArray,
+
+    #[error("Invalid SPIR-V: {reason}")]
+    InvalidSpirV { reason: String },
+
+    #[error("The shader doesn't have a {stage} entry point named {name}")]
+    MissingEntryPoint { name: String, stage: String },
+
+    #[error("Couldn't reflect shader: {reason}")]
+    ShaderReflectionError { reason: String },
+
+    #[error(
+        "Shader binding (set = {set}, binding = {binding}) doesn't match the RenderNode: {reason}"
+    )]
+    BindingMismatch {
+        set: u32,
+        binding: u32,
+        reason: String,
+    },
+
+    #[error("The RenderGraph contains a cycle between the passes: {passes:?}")]
+    RenderGraphCycle { passes: Vec<String> },
+
+    #[error("The attachment {name} isn't declared in the RenderGraph")]
+    UnknownAttachment { name: String },
+
+    #[error(
+        "The attachment {name} can't be read as a texture, only single sampled attachments can"
+    )]
+    AttachmentNotSampleable { name: String },
+
+    #[error("The pass {pass} reads the attachment {name} it also writes to")]
+    AttachmentReadWrite { pass: String, name: String },
+
+    #[error("The external attachment {name} wasn't provided when executing the RenderGraph")]
+    MissingExternalAttachment { name: String },
+
+    #[error("The attachment {name} has {attachment_samples} samples but {expected} are expected")]
+    AttachmentSampleCountMismatch {
+        name: String,
+        attachment_samples: u32,
+        expected: u32,
+    },
+
+    #[error("The multisampled color attachment {name} doesn't have a resolve target")]
+    MissingResolveTarget { name: String },
+
+    #[error("The sample count must be a power of two above 0 but is {sample_count}")]
+    InvalidSampleCount { sample_count: u32 },
+
+    #[error("Render pass attachment '{attachment}' is incompatible with the RenderNode: {reason}")]
+    RenderPassMismatch { attachment: String, reason: String },
+
+    #[error("Vertex shader input at location {location} doesn't match the RenderNode: {reason}")]
+    VertexInputMismatch { location: u32, reason: String },
 }
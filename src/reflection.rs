@@ -0,0 +1,1032 @@
+// Minimal SPIR-V reflection, only parses what's needed to validate
+// the shader interface against the pipeline layout built by the RenderNodeBuilder.
+use crate::RenderError;
+use std::collections::{HashMap, HashSet};
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+const HEADER_LENGTH: usize = 5;
+
+// Opcodes
+const OP_NAME: u32 = 5;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_EXECUTION_MODE: u32 = 16;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_FUNCTION: u32 = 54;
+const OP_FUNCTION_END: u32 = 56;
+const OP_FUNCTION_CALL: u32 = 57;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+// Execution models
+const EXECUTION_MODEL_VERTEX: u32 = 0;
+const EXECUTION_MODEL_FRAGMENT: u32 = 4;
+const EXECUTION_MODEL_GL_COMPUTE: u32 = 5;
+
+// Execution modes
+const EXECUTION_MODE_LOCAL_SIZE: u32 = 17;
+
+// Decorations
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_NON_WRITABLE: u32 = 24;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+// Storage classes
+const STORAGE_CLASS_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_CLASS_INPUT: u32 = 1;
+const STORAGE_CLASS_UNIFORM: u32 = 2;
+const STORAGE_CLASS_STORAGE_BUFFER: u32 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ScalarKind {
+    Float,
+    Sint,
+    Uint,
+    Bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ReflectedBindingType {
+    UniformBuffer {
+        size: u64,
+    },
+    StorageBuffer {
+        // Runtime sized arrays contribute with 0 bytes
+        size: u64,
+        readonly: bool,
+    },
+    SampledTexture {
+        dimension: wgpu::TextureViewDimension,
+        multisampled: bool,
+    },
+    StorageTexture {
+        dimension: wgpu::TextureViewDimension,
+    },
+    Sampler,
+    // Not supported by wgpu but reported so it can give a proper error
+    CombinedImageSampler,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ReflectedBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub name: Option<String>,
+    pub ty: ReflectedBindingType,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ReflectedInput {
+    pub location: u32,
+    // Matrices and arrays take up several consecutive locations
+    pub location_count: u32,
+    pub name: Option<String>,
+    pub kind: ScalarKind,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ReflectedEntryPoint {
+    pub name: String,
+    // None for execution models that can't be used with wgpu
+    pub stage: Option<wgpu::ShaderStage>,
+    // Only the resources and inputs statically used by the entry point
+    pub bindings: Vec<ReflectedBinding>,
+    pub inputs: Vec<ReflectedInput>,
+    // Only present for compute shaders
+    pub workgroup_size: Option<[u32; 3]>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ShaderReflection {
+    pub entry_points: Vec<ReflectedEntryPoint>,
+}
+
+#[derive(Debug, Clone)]
+enum SpirvType {
+    Bool,
+    Int {
+        width: u32,
+        signed: bool,
+    },
+    Float {
+        width: u32,
+    },
+    Vector {
+        component: u32,
+        count: u32,
+    },
+    Matrix {
+        column: u32,
+        count: u32,
+    },
+    Image {
+        dim: u32,
+        arrayed: bool,
+        multisampled: bool,
+        sampled: u32,
+    },
+    Sampler,
+    SampledImage,
+    Array {
+        element: u32,
+        length: u32,
+    },
+    RuntimeArray {
+        element: u32,
+    },
+    Struct {
+        members: Vec<u32>,
+    },
+    Pointer {
+        pointee: u32,
+    },
+}
+
+#[derive(Default)]
+struct Decorations {
+    set: Option<u32>,
+    binding: Option<u32>,
+    location: Option<u32>,
+    array_stride: Option<u32>,
+    built_in: bool,
+    block: bool,
+    buffer_block: bool,
+    non_writable: bool,
+}
+
+#[derive(Default)]
+struct MemberDecorations {
+    offset: Option<u32>,
+    matrix_stride: Option<u32>,
+    non_writable: bool,
+}
+
+struct EntryPoint {
+    execution_model: u32,
+    function: u32,
+    name: String,
+    interface: Vec<u32>,
+}
+
+// Every id referenced within a function and the functions it calls
+#[derive(Default)]
+struct Function {
+    referenced_ids: HashSet<u32>,
+    calls: Vec<u32>,
+}
+
+#[derive(Default)]
+struct Module {
+    names: HashMap<u32, String>,
+    types: HashMap<u32, SpirvType>,
+    constants: HashMap<u32, u32>,
+    decorations: HashMap<u32, Decorations>,
+    member_decorations: HashMap<(u32, u32), MemberDecorations>,
+    // (result type, id, storage class)
+    variables: Vec<(u32, u32, u32)>,
+    entry_points: Vec<EntryPoint>,
+    // Keyed by the entry point function
+    workgroup_sizes: HashMap<u32, [u32; 3]>,
+    functions: HashMap<u32, Function>,
+    current_function: Option<u32>,
+}
+
+fn reflection_error(reason: impl Into<String>) -> RenderError {
+    RenderError::ShaderReflectionError {
+        reason: reason.into(),
+    }
+}
+
+// The number of words a literal string with its null terminator occupies
+fn string_word_count(string: &str) -> usize {
+    string.len() / 4 + 1
+}
+
+fn parse_string(words: &[u32]) -> String {
+    let mut bytes = Vec::new();
+    'outer: for word in words {
+        for byte in word.to_le_bytes().iter() {
+            if *byte == 0 {
+                break 'outer;
+            }
+            bytes.push(*byte);
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+impl Module {
+    fn parse(spirv: &[u32]) -> Result<Module, RenderError> {
+        if spirv.len() < HEADER_LENGTH || spirv[0] != SPIRV_MAGIC {
+            return Err(reflection_error("Invalid SPIR-V header"));
+        }
+        let mut module = Module::default();
+        let mut position = HEADER_LENGTH;
+        while position < spirv.len() {
+            let word_count = (spirv[position] >> 16) as usize;
+            let opcode = spirv[position] & 0xffff;
+            if word_count == 0 || position + word_count > spirv.len() {
+                return Err(reflection_error("Truncated SPIR-V instruction"));
+            }
+            let operands = &spirv[position + 1..position + word_count];
+            module.parse_instruction(opcode, operands)?;
+            position += word_count;
+        }
+        Ok(module)
+    }
+
+    fn parse_instruction(&mut self, opcode: u32, operands: &[u32]) -> Result<(), RenderError> {
+        let operand = |index: usize| {
+            operands
+                .get(index)
+                .copied()
+                .ok_or_else(|| reflection_error(format!("Missing operand for opcode {}", opcode)))
+        };
+        let operands_from = |start: usize| {
+            operands
+                .get(start..)
+                .ok_or_else(|| reflection_error(format!("Missing operands for opcode {}", opcode)))
+        };
+        if let Some(function) = self.current_function {
+            let function = self.functions.entry(function).or_default();
+            function.referenced_ids.extend(operands.iter().copied());
+            if opcode == OP_FUNCTION_CALL {
+                function.calls.push(operand(2)?);
+            }
+        }
+        match opcode {
+            OP_NAME => {
+                let name = parse_string(operands_from(1)?);
+                self.names.insert(operand(0)?, name);
+            }
+            OP_ENTRY_POINT => {
+                let name = parse_string(operands_from(2)?);
+                let interface_start = (2 + string_word_count(&name)).min(operands.len());
+                self.entry_points.push(EntryPoint {
+                    execution_model: operand(0)?,
+                    function: operand(1)?,
+                    name,
+                    interface: operands[interface_start..].to_vec(),
+                });
+            }
+            OP_EXECUTION_MODE => {
+                if operand(1)? == EXECUTION_MODE_LOCAL_SIZE {
+                    self.workgroup_sizes
+                        .insert(operand(0)?, [operand(2)?, operand(3)?, operand(4)?]);
+                }
+            }
+            OP_FUNCTION => {
+                self.current_function = Some(operand(1)?);
+            }
+            OP_FUNCTION_END => {
+                self.current_function = None;
+            }
+            OP_TYPE_BOOL => {
+                self.types.insert(operand(0)?, SpirvType::Bool);
+            }
+            OP_TYPE_INT => {
+                self.types.insert(
+                    operand(0)?,
+                    SpirvType::Int {
+                        width: operand(1)?,
+                        signed: operand(2)? == 1,
+                    },
+                );
+            }
+            OP_TYPE_FLOAT => {
+                self.types
+                    .insert(operand(0)?, SpirvType::Float { width: operand(1)? });
+            }
+            OP_TYPE_VECTOR => {
+                self.types.insert(
+                    operand(0)?,
+                    SpirvType::Vector {
+                        component: operand(1)?,
+                        count: operand(2)?,
+                    },
+                );
+            }
+            OP_TYPE_MATRIX => {
+                self.types.insert(
+                    operand(0)?,
+                    SpirvType::Matrix {
+                        column: operand(1)?,
+                        count: operand(2)?,
+                    },
+                );
+            }
+            OP_TYPE_IMAGE => {
+                self.types.insert(
+                    operand(0)?,
+                    SpirvType::Image {
+                        dim: operand(2)?,
+                        arrayed: operand(4)? == 1,
+                        multisampled: operand(5)? == 1,
+                        sampled: operand(6)?,
+                    },
+                );
+            }
+            OP_TYPE_SAMPLER => {
+                self.types.insert(operand(0)?, SpirvType::Sampler);
+            }
+            OP_TYPE_SAMPLED_IMAGE => {
+                self.types.insert(operand(0)?, SpirvType::SampledImage);
+            }
+            OP_TYPE_ARRAY => {
+                let length_id = operand(2)?;
+                let length = *self.constants.get(&length_id).ok_or_else(|| {
+                    reflection_error("Array length must be a constant defined before use")
+                })?;
+                self.types.insert(
+                    operand(0)?,
+                    SpirvType::Array {
+                        element: operand(1)?,
+                        length,
+                    },
+                );
+            }
+            OP_TYPE_RUNTIME_ARRAY => {
+                self.types.insert(
+                    operand(0)?,
+                    SpirvType::RuntimeArray {
+                        element: operand(1)?,
+                    },
+                );
+            }
+            OP_TYPE_STRUCT => {
+                self.types.insert(
+                    operand(0)?,
+                    SpirvType::Struct {
+                        members: operands_from(1)?.to_vec(),
+                    },
+                );
+            }
+            OP_TYPE_POINTER => {
+                self.types.insert(
+                    operand(0)?,
+                    SpirvType::Pointer {
+                        pointee: operand(2)?,
+                    },
+                );
+            }
+            OP_CONSTANT => {
+                // Only 32 bit integer constants are of interest (array lengths)
+                if operands.len() == 3 {
+                    self.constants.insert(operand(1)?, operand(2)?);
+                }
+            }
+            OP_VARIABLE => {
+                self.variables.push((operand(0)?, operand(1)?, operand(2)?));
+            }
+            OP_DECORATE => {
+                let decorations = self.decorations.entry(operand(0)?).or_default();
+                match operand(1)? {
+                    DECORATION_BLOCK => decorations.block = true,
+                    DECORATION_BUFFER_BLOCK => decorations.buffer_block = true,
+                    DECORATION_ARRAY_STRIDE => decorations.array_stride = Some(operand(2)?),
+                    DECORATION_BUILT_IN => decorations.built_in = true,
+                    DECORATION_NON_WRITABLE => decorations.non_writable = true,
+                    DECORATION_LOCATION => decorations.location = Some(operand(2)?),
+                    DECORATION_BINDING => decorations.binding = Some(operand(2)?),
+                    DECORATION_DESCRIPTOR_SET => decorations.set = Some(operand(2)?),
+                    _ => {}
+                }
+            }
+            OP_MEMBER_DECORATE => {
+                let member_decorations = self
+                    .member_decorations
+                    .entry((operand(0)?, operand(1)?))
+                    .or_default();
+                match operand(2)? {
+                    DECORATION_OFFSET => member_decorations.offset = Some(operand(3)?),
+                    DECORATION_MATRIX_STRIDE => {
+                        member_decorations.matrix_stride = Some(operand(3)?)
+                    }
+                    DECORATION_NON_WRITABLE => member_decorations.non_writable = true,
+                    DECORATION_BUILT_IN => {
+                        self.decorations.entry(operand(0)?).or_default().built_in = true
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn get_type(&self, id: u32) -> Result<&SpirvType, RenderError> {
+        self.types
+            .get(&id)
+            .ok_or_else(|| reflection_error(format!("Unknown type id {}", id)))
+    }
+
+    fn scalar_size(&self, id: u32) -> Result<u64, RenderError> {
+        match self.get_type(id)? {
+            SpirvType::Int { width, .. } | SpirvType::Float { width } => Ok(*width as u64 / 8),
+            SpirvType::Bool => Ok(4),
+            _ => Err(reflection_error("Expected a scalar type")),
+        }
+    }
+
+    // Size in bytes of a type placed in a buffer, matrix_stride comes from the
+    // member decoration since it isn't part of the matrix type itself
+    fn type_size(&self, id: u32, matrix_stride: Option<u32>) -> Result<u64, RenderError> {
+        match self.get_type(id)? {
+            SpirvType::Bool | SpirvType::Int { .. } | SpirvType::Float { .. } => {
+                self.scalar_size(id)
+            }
+            SpirvType::Vector { component, count } => {
+                Ok(self.scalar_size(*component)? * *count as u64)
+            }
+            SpirvType::Matrix { column, count } => match matrix_stride {
+                Some(stride) => Ok(stride as u64 * *count as u64),
+                None => Ok(self.type_size(*column, None)? * *count as u64),
+            },
+            SpirvType::Array { element, length } => {
+                let stride = match self.decorations.get(&id).and_then(|d| d.array_stride) {
+                    Some(stride) => stride as u64,
+                    None => self.type_size(*element, matrix_stride)?,
+                };
+                Ok(stride * *length as u64)
+            }
+            SpirvType::RuntimeArray { .. } => Ok(0),
+            SpirvType::Struct { members } => {
+                let mut size = 0;
+                for (i, member) in members.iter().enumerate() {
+                    let member_decorations = self.member_decorations.get(&(id, i as u32));
+                    let offset = member_decorations
+                        .and_then(|d| d.offset)
+                        .unwrap_or(size as u32) as u64;
+                    let member_size =
+                        self.type_size(*member, member_decorations.and_then(|d| d.matrix_stride))?;
+                    size = size.max(offset + member_size);
+                }
+                Ok(size)
+            }
+            _ => Err(reflection_error(format!(
+                "Type id {} can't be placed in a buffer",
+                id
+            ))),
+        }
+    }
+
+    fn scalar_kind(&self, id: u32) -> Result<ScalarKind, RenderError> {
+        match self.get_type(id)? {
+            SpirvType::Bool => Ok(ScalarKind::Bool),
+            SpirvType::Float { .. } => Ok(ScalarKind::Float),
+            SpirvType::Int { signed: true, .. } => Ok(ScalarKind::Sint),
+            SpirvType::Int { signed: false, .. } => Ok(ScalarKind::Uint),
+            SpirvType::Vector { component, .. } => self.scalar_kind(*component),
+            SpirvType::Matrix { column, .. } => self.scalar_kind(*column),
+            SpirvType::Array { element, .. } => self.scalar_kind(*element),
+            _ => Err(reflection_error(
+                "Shader inputs must be made out of scalars",
+            )),
+        }
+    }
+
+    fn location_count(&self, id: u32) -> Result<u32, RenderError> {
+        match self.get_type(id)? {
+            SpirvType::Matrix { count, .. } => Ok(*count),
+            SpirvType::Array { element, length } => Ok(length * self.location_count(*element)?),
+            _ => Ok(1),
+        }
+    }
+
+    fn image_dimension(dim: u32, arrayed: bool) -> Result<wgpu::TextureViewDimension, RenderError> {
+        match (dim, arrayed) {
+            (0, false) => Ok(wgpu::TextureViewDimension::D1),
+            (1, false) => Ok(wgpu::TextureViewDimension::D2),
+            (1, true) => Ok(wgpu::TextureViewDimension::D2Array),
+            (2, false) => Ok(wgpu::TextureViewDimension::D3),
+            (3, false) => Ok(wgpu::TextureViewDimension::Cube),
+            (3, true) => Ok(wgpu::TextureViewDimension::CubeArray),
+            _ => Err(reflection_error(format!(
+                "Unsupported image dimension {} (arrayed: {})",
+                dim, arrayed
+            ))),
+        }
+    }
+
+    fn binding_type(
+        &self,
+        storage_class: u32,
+        type_id: u32,
+    ) -> Result<ReflectedBindingType, RenderError> {
+        let decorations = self.decorations.get(&type_id);
+        let is_readonly = |members: &[u32]| {
+            (0..members.len() as u32).all(|i| {
+                self.member_decorations
+                    .get(&(type_id, i))
+                    .map_or(false, |d| d.non_writable)
+            })
+        };
+        match (storage_class, self.get_type(type_id)?) {
+            // Arrays of resources are validated by their element type
+            (_, SpirvType::Array { element, .. }) | (_, SpirvType::RuntimeArray { element }) => {
+                self.binding_type(storage_class, *element)
+            }
+            (STORAGE_CLASS_UNIFORM, SpirvType::Struct { members })
+                if decorations.map_or(false, |d| d.buffer_block) =>
+            {
+                Ok(ReflectedBindingType::StorageBuffer {
+                    size: self.type_size(type_id, None)?,
+                    readonly: is_readonly(members),
+                })
+            }
+            (STORAGE_CLASS_UNIFORM, SpirvType::Struct { .. }) => {
+                Ok(ReflectedBindingType::UniformBuffer {
+                    size: self.type_size(type_id, None)?,
+                })
+            }
+            (STORAGE_CLASS_STORAGE_BUFFER, SpirvType::Struct { members }) => {
+                Ok(ReflectedBindingType::StorageBuffer {
+                    size: self.type_size(type_id, None)?,
+                    readonly: is_readonly(members),
+                })
+            }
+            (STORAGE_CLASS_UNIFORM_CONSTANT, SpirvType::Sampler) => {
+                Ok(ReflectedBindingType::Sampler)
+            }
+            (STORAGE_CLASS_UNIFORM_CONSTANT, SpirvType::SampledImage) => {
+                Ok(ReflectedBindingType::CombinedImageSampler)
+            }
+            (
+                STORAGE_CLASS_UNIFORM_CONSTANT,
+                SpirvType::Image {
+                    dim,
+                    arrayed,
+                    multisampled,
+                    sampled,
+                },
+            ) => {
+                let dimension = Self::image_dimension(*dim, *arrayed)?;
+                if *sampled == 2 {
+                    Ok(ReflectedBindingType::StorageTexture { dimension })
+                } else {
+                    Ok(ReflectedBindingType::SampledTexture {
+                        dimension,
+                        multisampled: *multisampled,
+                    })
+                }
+            }
+            _ => Err(reflection_error(format!(
+                "Unsupported resource type with id {}",
+                type_id
+            ))),
+        }
+    }
+
+    // Global ids statically used by the function, including the functions it calls
+    fn used_ids(&self, function: u32) -> HashSet<u32> {
+        let mut used = HashSet::new();
+        let mut visited = HashSet::new();
+        let mut pending = vec![function];
+        while let Some(function) = pending.pop() {
+            if !visited.insert(function) {
+                continue;
+            }
+            if let Some(function) = self.functions.get(&function) {
+                used.extend(function.referenced_ids.iter().copied());
+                pending.extend(function.calls.iter().copied());
+            }
+        }
+        used
+    }
+
+    fn reflect(&self) -> Result<ShaderReflection, RenderError> {
+        // Every resource and input variable of the module keyed by id
+        let mut bindings = HashMap::new();
+        let mut inputs = HashMap::new();
+        for (pointer_type, id, storage_class) in self.variables.iter() {
+            let decorations = match self.decorations.get(id) {
+                Some(decorations) => decorations,
+                None => continue,
+            };
+            let pointee = match self.get_type(*pointer_type)? {
+                SpirvType::Pointer { pointee } => *pointee,
+                _ => return Err(reflection_error("Variables must have pointer types")),
+            };
+            let name = self.names.get(id).cloned();
+            if let (Some(set), Some(binding)) = (decorations.set, decorations.binding) {
+                bindings.insert(
+                    *id,
+                    ReflectedBinding {
+                        set,
+                        binding,
+                        name,
+                        ty: self.binding_type(*storage_class, pointee)?,
+                    },
+                );
+            } else if *storage_class == STORAGE_CLASS_INPUT {
+                let is_built_in = decorations.built_in
+                    || self.decorations.get(&pointee).map_or(false, |d| d.built_in);
+                if let (Some(location), false) = (decorations.location, is_built_in) {
+                    inputs.insert(
+                        *id,
+                        ReflectedInput {
+                            location,
+                            location_count: self.location_count(pointee)?,
+                            name,
+                            kind: self.scalar_kind(pointee)?,
+                        },
+                    );
+                }
+            }
+        }
+
+        let entry_points = self
+            .entry_points
+            .iter()
+            .map(|entry_point| {
+                let mut used = self.used_ids(entry_point.function);
+                used.extend(entry_point.interface.iter().copied());
+                let mut bindings = bindings
+                    .iter()
+                    .filter(|(id, _)| used.contains(id))
+                    .map(|(_, binding)| binding.clone())
+                    .collect::<Vec<_>>();
+                bindings.sort_by_key(|b| (b.set, b.binding));
+                let mut inputs = inputs
+                    .iter()
+                    .filter(|(id, _)| used.contains(id))
+                    .map(|(_, input)| input.clone())
+                    .collect::<Vec<_>>();
+                inputs.sort_by_key(|input| input.location);
+                let stage = match entry_point.execution_model {
+                    EXECUTION_MODEL_VERTEX => Some(wgpu::ShaderStage::VERTEX),
+                    EXECUTION_MODEL_FRAGMENT => Some(wgpu::ShaderStage::FRAGMENT),
+                    EXECUTION_MODEL_GL_COMPUTE => Some(wgpu::ShaderStage::COMPUTE),
+                    _ => None,
+                };
+                ReflectedEntryPoint {
+                    name: entry_point.name.clone(),
+                    stage,
+                    bindings,
+                    inputs,
+                    workgroup_size: self.workgroup_sizes.get(&entry_point.function).copied(),
+                }
+            })
+            .collect();
+        Ok(ShaderReflection { entry_points })
+    }
+}
+
+impl ShaderReflection {
+    pub(crate) fn new(spirv: &[u32]) -> Result<ShaderReflection, RenderError> {
+        Module::parse(spirv)?.reflect()
+    }
+
+    /// Finds the entry point with the given name that can be used for the stage
+    pub(crate) fn get_entry_point(
+        &self,
+        name: &str,
+        stage: wgpu::ShaderStage,
+    ) -> Result<&ReflectedEntryPoint, RenderError> {
+        self.entry_points
+            .iter()
+            .find(|entry_point| entry_point.name == name && entry_point.stage == Some(stage))
+            .ok_or_else(|| RenderError::MissingEntryPoint {
+                name: name.to_string(),
+                stage: format!("{:?}", stage),
+            })
+    }
+}
+
+fn binding_name(binding: &ReflectedBinding) -> String {
+    match &binding.name {
+        Some(name) if !name.is_empty() => format!(
+            "{} (set = {}, binding = {})",
+            name, binding.set, binding.binding
+        ),
+        _ => format!("(set = {}, binding = {})", binding.set, binding.binding),
+    }
+}
+
+fn describe(ty: &wgpu::BindingType) -> &'static str {
+    match ty {
+        wgpu::BindingType::UniformBuffer { .. } => "uniform buffer",
+        wgpu::BindingType::StorageBuffer { .. } => "storage buffer",
+        wgpu::BindingType::Sampler { .. } => "sampler",
+        wgpu::BindingType::SampledTexture { .. } => "sampled texture",
+        wgpu::BindingType::StorageTexture { .. } => "storage texture",
+    }
+}
+
+fn check_binding(
+    reflected: &ReflectedBinding,
+    entry: &wgpu::BindGroupLayoutEntry,
+    stage: wgpu::ShaderStage,
+) -> Result<(), RenderError> {
+    let mismatch = |reason: String| RenderError::BindingMismatch {
+        set: reflected.set,
+        binding: reflected.binding,
+        reason,
+    };
+    if !entry.visibility.contains(stage) {
+        return Err(mismatch(format!(
+            "{} is used in the {:?} stage but is only visible in {:?}",
+            binding_name(reflected),
+            stage,
+            entry.visibility
+        )));
+    }
+    match (&reflected.ty, &entry.ty) {
+        (
+            ReflectedBindingType::StorageBuffer {
+                readonly: false, ..
+            },
+            wgpu::BindingType::StorageBuffer { readonly: true, .. },
+        ) => Err(mismatch(format!(
+            "{} is writable in the shader but the storage binding is read only",
+            binding_name(reflected)
+        ))),
+        (
+            ReflectedBindingType::UniformBuffer { size },
+            wgpu::BindingType::UniformBuffer {
+                min_binding_size, ..
+            },
+        )
+        | (
+            ReflectedBindingType::StorageBuffer { size, .. },
+            wgpu::BindingType::StorageBuffer {
+                min_binding_size, ..
+            },
+        ) => match min_binding_size {
+            Some(bound_size) if bound_size.get() < *size => Err(mismatch(format!(
+                "{} needs {} bytes but the bound GpuData is only {} bytes",
+                binding_name(reflected),
+                size,
+                bound_size.get()
+            ))),
+            _ => Ok(()),
+        },
+        (ReflectedBindingType::Sampler, wgpu::BindingType::Sampler { .. }) => Ok(()),
+        (
+            ReflectedBindingType::SampledTexture {
+                dimension,
+                multisampled,
+            },
+            wgpu::BindingType::SampledTexture {
+                dimension: layout_dimension,
+                multisampled: layout_multisampled,
+                ..
+            },
+        ) => {
+            if dimension != layout_dimension || multisampled != layout_multisampled {
+                Err(mismatch(format!(
+                    "{} is a {:?} texture (multisampled: {}) but the layout declares {:?} (multisampled: {})",
+                    binding_name(reflected),
+                    dimension,
+                    multisampled,
+                    layout_dimension,
+                    layout_multisampled
+                )))
+            } else {
+                Ok(())
+            }
+        }
+        (
+            ReflectedBindingType::StorageTexture { dimension },
+            wgpu::BindingType::StorageTexture {
+                dimension: layout_dimension,
+                ..
+            },
+        ) => {
+            if dimension != layout_dimension {
+                Err(mismatch(format!(
+                    "{} is a {:?} storage texture but the layout declares {:?}",
+                    binding_name(reflected),
+                    dimension,
+                    layout_dimension
+                )))
+            } else {
+                Ok(())
+            }
+        }
+        (ReflectedBindingType::CombinedImageSampler, _) => Err(mismatch(format!(
+            "{} is a combined image sampler which isn't supported, use separate texture and sampler bindings",
+            binding_name(reflected)
+        ))),
+        (reflected_type, layout_type) => Err(mismatch(format!(
+            "{} is declared as {:?} in the shader but as a {} in the layout",
+            binding_name(reflected),
+            reflected_type,
+            describe(layout_type)
+        ))),
+    }
+}
+
+/// Checks every resource the shader uses against the bind group layouts
+/// in the order they will be set in the pipeline layout
+pub(crate) fn validate_bindings(
+    entry_point: &ReflectedEntryPoint,
+    stage: wgpu::ShaderStage,
+    bind_group_entries: &[&[wgpu::BindGroupLayoutEntry]],
+) -> Result<(), RenderError> {
+    for reflected in entry_point.bindings.iter() {
+        let entries = bind_group_entries
+            .get(reflected.set as usize)
+            .ok_or_else(|| RenderError::BindingMismatch {
+                set: reflected.set,
+                binding: reflected.binding,
+                reason: format!(
+                    "{} uses a bind group that isn't registered, only {} bind groups are added to the RenderNode",
+                    binding_name(reflected),
+                    bind_group_entries.len()
+                ),
+            })?;
+        // Texture layouts that don't provide their entries can't be validated
+        if entries.is_empty() {
+            continue;
+        }
+        let entry = entries
+            .iter()
+            .find(|entry| entry.binding == reflected.binding)
+            .ok_or_else(|| RenderError::BindingMismatch {
+                set: reflected.set,
+                binding: reflected.binding,
+                reason: format!(
+                    "{} isn't part of the bind group registered at that index",
+                    binding_name(reflected)
+                ),
+            })?;
+        check_binding(reflected, entry, stage)?;
+    }
+    Ok(())
+}
+
+fn vertex_format_kind(format: wgpu::VertexFormat) -> ScalarKind {
+    use wgpu::VertexFormat::*;
+    match format {
+        Uchar2 | Uchar4 | Ushort2 | Ushort4 | Uint | Uint2 | Uint3 | Uint4 => ScalarKind::Uint,
+        Char2 | Char4 | Short2 | Short4 | Int | Int2 | Int3 | Int4 => ScalarKind::Sint,
+        _ => ScalarKind::Float,
+    }
+}
+
+/// Checks that every vertex shader input is provided by one of the
+/// registered VertexBuffers with a compatible format
+pub(crate) fn validate_vertex_inputs(
+    entry_point: &ReflectedEntryPoint,
+    vertex_buffers: &[wgpu::VertexBufferDescriptor],
+) -> Result<(), RenderError> {
+    let attributes = vertex_buffers
+        .iter()
+        .flat_map(|descriptor| descriptor.attributes.iter())
+        .collect::<Vec<_>>();
+    for input in entry_point.inputs.iter() {
+        let name = input.name.as_deref().unwrap_or("input");
+        for location in input.location..input.location + input.location_count {
+            let attribute = attributes
+                .iter()
+                .find(|attribute| attribute.shader_location == location)
+                .ok_or_else(|| RenderError::VertexInputMismatch {
+                    location,
+                    reason: format!("{} isn't provided by any registered VertexBuffer", name),
+                })?;
+            let format_kind = vertex_format_kind(attribute.format);
+            if format_kind != input.kind {
+                return Err(RenderError::VertexInputMismatch {
+                    location,
+                    reason: format!(
+                        "{} expects {:?} data but the VertexBuffer provides {:?} ({:?})",
+                        name, input.kind, format_kind, attribute.format
+                    ),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "shaderc"))]
+mod tests {
+
+    use super::*;
+    use crate::shader::{compile_glsl, ShaderCompileOptions};
+    use shaderc::ShaderKind;
+
+    #[test]
+    fn reflect_example_shaders() -> Result<(), RenderError> {
+        let vertex = compile_glsl(
+            "examples/basic/shader.vs",
+            ShaderKind::Vertex,
+            &ShaderCompileOptions::default(),
+        )?
+        .spirv;
+        let reflection = ShaderReflection::new(&vertex)?;
+        let reflection = reflection.get_entry_point("main", wgpu::ShaderStage::VERTEX)?;
+        assert_eq!(reflection.inputs.len(), 2);
+        assert_eq!(reflection.inputs[0].location, 0);
+        assert_eq!(reflection.inputs[1].location, 1);
+        assert_eq!(reflection.bindings.len(), 2);
+        assert_eq!(
+            reflection.bindings[0].ty,
+            ReflectedBindingType::UniformBuffer { size: 140 }
+        );
+        assert_eq!(
+            reflection.bindings[1].ty,
+            ReflectedBindingType::UniformBuffer { size: 64 }
+        );
+
+        let fragment = compile_glsl(
+            "examples/basic/shader.fs",
+            ShaderKind::Fragment,
+            &ShaderCompileOptions::default(),
+        )?
+        .spirv;
+        let reflection = ShaderReflection::new(&fragment)?;
+        let reflection = reflection.get_entry_point("main", wgpu::ShaderStage::FRAGMENT)?;
+        assert_eq!(reflection.bindings.len(), 2);
+        assert_eq!(
+            reflection.bindings[0].ty,
+            ReflectedBindingType::SampledTexture {
+                dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            }
+        );
+        assert_eq!(reflection.bindings[1].ty, ReflectedBindingType::Sampler);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_vertex_input() -> Result<(), RenderError> {
+        let vertex = compile_glsl(
+            "examples/basic/shader.vs",
+            ShaderKind::Vertex,
+            &ShaderCompileOptions::default(),
+        )?
+        .spirv;
+        let reflection = ShaderReflection::new(&vertex)?;
+        let entry_point = reflection.get_entry_point("main", wgpu::ShaderStage::VERTEX)?;
+        let attributes = wgpu::vertex_attr_array![0 => Float3];
+        let descriptors = [wgpu::VertexBufferDescriptor {
+            stride: 12,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &attributes,
+        }];
+        match validate_vertex_inputs(entry_point, &descriptors) {
+            Err(RenderError::VertexInputMismatch { location, .. }) => assert_eq!(location, 1),
+            _ => panic!("Missing vertex input wasn't detected"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn malformed_spirv() {
+        let header = [SPIRV_MAGIC, 0x0001_0000, 0, 1, 0];
+        // Instructions with fewer operands than the opcode requires
+        for opcode in &[OP_NAME, OP_TYPE_STRUCT, OP_ENTRY_POINT] {
+            let mut spirv = header.to_vec();
+            spirv.push((1 << 16) | opcode);
+            match ShaderReflection::new(&spirv) {
+                Err(RenderError::ShaderReflectionError { .. }) => {}
+                _ => panic!("Opcode {} without operands wasn't rejected", opcode),
+            }
+        }
+        // An instruction that claims more words than are left
+        let mut spirv = header.to_vec();
+        spirv.push((3 << 16) | OP_NAME);
+        assert!(ShaderReflection::new(&spirv).is_err());
+        assert!(ShaderReflection::new(&header[..3]).is_err());
+    }
+
+    #[test]
+    fn missing_entry_point() -> Result<(), RenderError> {
+        let vertex = compile_glsl(
+            "examples/basic/shader.vs",
+            ShaderKind::Vertex,
+            &ShaderCompileOptions::default(),
+        )?
+        .spirv;
+        let reflection = ShaderReflection::new(&vertex)?;
+        assert!(reflection
+            .get_entry_point("main", wgpu::ShaderStage::FRAGMENT)
+            .is_err());
+        match reflection.get_entry_point("vs_main", wgpu::ShaderStage::VERTEX) {
+            Err(RenderError::MissingEntryPoint { name, .. }) => assert_eq!(name, "vs_main"),
+            _ => panic!("Missing entry point wasn't detected"),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,600 @@
+use crate::{
+    render_node::{RenderNode, RenderNodeRunner},
+    textures::{simpletexture::SimpleTexture, TextureData},
+    RenderError,
+};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AttachmentSize {
+    /// Follows the size given to the graph in build and resize, typically the swap chain size
+    Output,
+    Fixed {
+        width: u32,
+        height: u32,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AttachmentDescriptor {
+    pub format: wgpu::TextureFormat,
+    pub size: AttachmentSize,
+    pub sample_count: u32,
+}
+
+struct ColorWrite {
+    attachment: &'static str,
+    resolve_target: Option<&'static str>,
+    ops: wgpu::Operations<wgpu::Color>,
+}
+
+struct DepthWrite {
+    attachment: &'static str,
+    depth_ops: Option<wgpu::Operations<f32>>,
+    stencil_ops: Option<wgpu::Operations<u32>>,
+}
+
+/// A RenderNode together with the attachments it reads from and writes to
+pub struct GraphPass {
+    name: &'static str,
+    node: RenderNode,
+    reads: Vec<&'static str>,
+    color_writes: Vec<ColorWrite>,
+    depth_write: Option<DepthWrite>,
+}
+
+impl GraphPass {
+    pub fn new(name: &'static str, node: RenderNode) -> Self {
+        GraphPass {
+            name,
+            node,
+            reads: Vec::new(),
+            color_writes: Vec::new(),
+            depth_write: None,
+        }
+    }
+
+    /// The attachment will be available as a sampled texture through the PassContext
+    pub fn read(mut self, attachment: &'static str) -> Self {
+        self.reads.push(attachment);
+        self
+    }
+
+    pub fn write_color(
+        mut self,
+        attachment: &'static str,
+        ops: wgpu::Operations<wgpu::Color>,
+    ) -> Self {
+        self.color_writes.push(ColorWrite {
+            attachment,
+            resolve_target: None,
+            ops,
+        });
+        self
+    }
+
+    pub fn write_resolved_color(
+        mut self,
+        attachment: &'static str,
+        resolve_target: &'static str,
+        ops: wgpu::Operations<wgpu::Color>,
+    ) -> Self {
+        self.color_writes.push(ColorWrite {
+            attachment,
+            resolve_target: Some(resolve_target),
+            ops,
+        });
+        self
+    }
+
+    pub fn write_depth_stencil(
+        mut self,
+        attachment: &'static str,
+        depth_ops: Option<wgpu::Operations<f32>>,
+        stencil_ops: Option<wgpu::Operations<u32>>,
+    ) -> Self {
+        self.depth_write = Some(DepthWrite {
+            attachment,
+            depth_ops,
+            stencil_ops,
+        });
+        self
+    }
+
+    fn writes(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.color_writes
+            .iter()
+            .flat_map(|write| std::iter::once(write.attachment).chain(write.resolve_target))
+            .chain(self.depth_write.as_ref().map(|write| write.attachment))
+    }
+}
+
+enum AttachmentResource {
+    Sampled(TextureData<SimpleTexture>),
+    // Multisampled attachments can't be bound as a SimpleTexture
+    Multisampled {
+        _texture: wgpu::Texture,
+        view: wgpu::TextureView,
+    },
+}
+
+impl AttachmentResource {
+    fn view(&self) -> &wgpu::TextureView {
+        match self {
+            AttachmentResource::Sampled(data) => &data.views[0],
+            AttachmentResource::Multisampled { view, .. } => view,
+        }
+    }
+}
+
+/// Gives the recording closure access to the pass being recorded, the attachments
+/// of the graph and the user provided frame data
+pub struct PassContext<'a, D> {
+    pub name: &'static str,
+    pub data: &'a D,
+    attachments: &'a HashMap<&'static str, AttachmentResource>,
+}
+
+impl<'a, D> PassContext<'a, D> {
+    #[inline]
+    pub fn get_attachment_texture(
+        &self,
+        name: &str,
+    ) -> Result<&'a TextureData<SimpleTexture>, RenderError> {
+        match self.attachments.get(name) {
+            Some(AttachmentResource::Sampled(data)) => Ok(data),
+            Some(AttachmentResource::Multisampled { .. }) => {
+                Err(RenderError::AttachmentNotSampleable {
+                    name: name.to_string(),
+                })
+            }
+            None => Err(RenderError::UnknownAttachment {
+                name: name.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RenderGraphBuilder {
+    attachments: Vec<(&'static str, AttachmentDescriptor)>,
+    external_attachments: Vec<&'static str>,
+    passes: Vec<GraphPass>,
+}
+
+impl RenderGraphBuilder {
+    /// Transient attachment allocated and owned by the graph
+    pub fn add_attachment(mut self, name: &'static str, desc: AttachmentDescriptor) -> Self {
+        self.attachments.push((name, desc));
+        self
+    }
+
+    /// Attachment provided when the graph is executed, e.g the swap chain frame
+    pub fn add_external_attachment(mut self, name: &'static str) -> Self {
+        self.external_attachments.push(name);
+        self
+    }
+
+    pub fn add_pass(mut self, pass: GraphPass) -> Self {
+        self.passes.push(pass);
+        self
+    }
+
+    fn validate(&self) -> Result<(), RenderError> {
+        let is_declared = |name: &str| {
+            self.attachments
+                .iter()
+                .any(|(declared, _)| *declared == name)
+                || self
+                    .external_attachments
+                    .iter()
+                    .any(|external| *external == name)
+        };
+        for pass in self.passes.iter() {
+            if let Some(unknown) = pass
+                .reads
+                .iter()
+                .copied()
+                .chain(pass.writes())
+                .find(|name| !is_declared(name))
+            {
+                return Err(RenderError::UnknownAttachment {
+                    name: unknown.to_string(),
+                });
+            }
+            check_reads(&PassDependencies::from(pass))?;
+            // Resolve targets are always single sampled while the rest of
+            // the written attachments must match the node
+            let node_samples = pass.node.get_sample_count();
+            let written = pass
+                .color_writes
+                .iter()
+                .map(|write| (write.attachment, node_samples))
+                .chain(
+                    pass.color_writes
+                        .iter()
+                        .filter_map(|write| write.resolve_target)
+                        .map(|resolve_target| (resolve_target, 1)),
+                )
+                .chain(
+                    pass.depth_write
+                        .as_ref()
+                        .map(|write| (write.attachment, node_samples)),
+                );
+            for (name, expected) in written {
+                // External attachments are assumed to be single sampled
+                let attachment_samples = self
+                    .attachments
+                    .iter()
+                    .find(|(declared, _)| *declared == name)
+                    .map_or(1, |(_, desc)| desc.sample_count);
+                if attachment_samples != expected {
+                    return Err(RenderError::AttachmentSampleCountMismatch {
+                        name: name.to_string(),
+                        attachment_samples,
+                        expected,
+                    });
+                }
+            }
+            let color_formats = pass.node.get_color_formats();
+            let written_formats = pass
+                .color_writes
+                .iter()
+                .zip(color_formats.iter())
+                .flat_map(|(write, format)| {
+                    std::iter::once(write.attachment)
+                        .chain(write.resolve_target)
+                        .map(move |name| (name, *format))
+                })
+                .chain(pass.depth_write.as_ref().and_then(|write| {
+                    pass.node
+                        .get_depth_format()
+                        .map(|format| (write.attachment, format))
+                }));
+            for (name, expected) in written_formats {
+                if let Some((_, desc)) = self
+                    .attachments
+                    .iter()
+                    .find(|(declared, _)| *declared == name)
+                {
+                    if desc.format != expected {
+                        return Err(RenderError::RenderPassMismatch {
+                            attachment: name.to_string(),
+                            reason: format!(
+                                "the RenderNode of {} expects {:?} but the attachment is {:?}",
+                                pass.name, expected, desc.format
+                            ),
+                        });
+                    }
+                }
+            }
+            if node_samples > 1 {
+                if let Some(write) = pass
+                    .color_writes
+                    .iter()
+                    .find(|write| write.resolve_target.is_none())
+                {
+                    return Err(RenderError::MissingResolveTarget {
+                        name: write.attachment.to_string(),
+                    });
+                }
+            }
+            for read in pass.reads.iter() {
+                let sampleable = self
+                    .attachments
+                    .iter()
+                    .any(|(name, desc)| name == read && desc.sample_count == 1);
+                if !sampleable {
+                    return Err(RenderError::AttachmentNotSampleable {
+                        name: read.to_string(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn build(
+        self,
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+    ) -> Result<RenderGraph, RenderError> {
+        self.validate()?;
+        let dependencies = self
+            .passes
+            .iter()
+            .map(PassDependencies::from)
+            .collect::<Vec<_>>();
+        let order = sort_passes(&dependencies)?;
+        let mut graph = RenderGraph {
+            attachment_descriptors: self.attachments,
+            external_attachments: self.external_attachments,
+            attachments: HashMap::new(),
+            passes: self.passes,
+            order,
+        };
+        graph.resize(device, width, height);
+        Ok(graph)
+    }
+}
+
+pub struct RenderGraph {
+    attachment_descriptors: Vec<(&'static str, AttachmentDescriptor)>,
+    external_attachments: Vec<&'static str>,
+    attachments: HashMap<&'static str, AttachmentResource>,
+    passes: Vec<GraphPass>,
+    // Indices into passes in execution order
+    order: Vec<usize>,
+}
+
+impl RenderGraph {
+    pub fn builder() -> RenderGraphBuilder {
+        RenderGraphBuilder::default()
+    }
+
+    /// (Re)allocates all transient attachments, attachments with a fixed
+    /// size are only allocated the first time.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        for (name, desc) in self.attachment_descriptors.iter() {
+            let (width, height) = match desc.size {
+                AttachmentSize::Output => (width, height),
+                AttachmentSize::Fixed { width, height } => {
+                    if self.attachments.contains_key(name) {
+                        continue;
+                    }
+                    (width, height)
+                }
+            };
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(*name),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth: 1,
+                },
+                mip_level_count: 1,
+                sample_count: desc.sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: desc.format,
+                usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+            });
+            let view = texture.create_default_view();
+            let resource = if desc.sample_count == 1 {
+                let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                    label: None,
+                    address_mode_u: wgpu::AddressMode::ClampToEdge,
+                    address_mode_v: wgpu::AddressMode::ClampToEdge,
+                    address_mode_w: wgpu::AddressMode::ClampToEdge,
+                    mag_filter: wgpu::FilterMode::Linear,
+                    min_filter: wgpu::FilterMode::Linear,
+                    mipmap_filter: wgpu::FilterMode::Nearest,
+                    compare: Some(wgpu::CompareFunction::Always),
+                    ..Default::default()
+                });
+                AttachmentResource::Sampled(SimpleTexture::create_texture_data(
+                    device, texture, view, sampler,
+                ))
+            } else {
+                AttachmentResource::Multisampled {
+                    _texture: texture,
+                    view,
+                }
+            };
+            self.attachments.insert(*name, resource);
+        }
+    }
+
+    /// Reloads the changed shaders of every pass, see RenderNode::reload_shaders.
+    /// Returns true if any pipeline was rebuilt.
+    #[cfg(any(feature = "shaderc", feature = "wgsl"))]
+    pub fn reload_shaders(&mut self, device: &wgpu::Device) -> Result<bool, RenderError> {
+        let mut reloaded = false;
+        for pass in self.passes.iter_mut() {
+            reloaded |= pass.node.reload_shaders(device)?;
+        }
+        Ok(reloaded)
+    }
+
+    /// Records every pass in dependency order into the given encoder. The record closure is
+    /// called once per pass with a runner that already has the pass attachments set up.
+    pub fn execute<D, F>(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        external_attachments: &[(&str, &wgpu::TextureView)],
+        data: &D,
+        mut record: F,
+    ) -> Result<(), RenderError>
+    where
+        F: for<'r> FnMut(&PassContext<'r, D>, &mut RenderNodeRunner<'r, 'r>),
+    {
+        if let Some(missing) = self.external_attachments.iter().find(|name| {
+            !external_attachments
+                .iter()
+                .any(|(provided, _)| provided == *name)
+        }) {
+            return Err(RenderError::MissingExternalAttachment {
+                name: missing.to_string(),
+            });
+        }
+        let get_view = |name: &str| {
+            self.attachments
+                .get(name)
+                .map(AttachmentResource::view)
+                .or_else(|| {
+                    external_attachments
+                        .iter()
+                        .find(|(provided, _)| *provided == name)
+                        .map(|(_, view)| *view)
+                })
+                .expect("Attachments are validated when the graph is built")
+        };
+        for pass in self.order.iter().map(|&index| &self.passes[index]) {
+            let color_attachments = pass
+                .color_writes
+                .iter()
+                .map(|write| wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment: get_view(write.attachment),
+                    resolve_target: write.resolve_target.map(get_view),
+                    ops: write.ops,
+                })
+                .collect::<Vec<_>>();
+            let depth_stencil_attachment = pass.depth_write.as_ref().map(|write| {
+                wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                    attachment: get_view(write.attachment),
+                    depth_ops: write.depth_ops,
+                    stencil_ops: write.stencil_ops,
+                }
+            });
+            let mut runner = pass.node.try_runner(
+                encoder,
+                wgpu::RenderPassDescriptor {
+                    color_attachments: &color_attachments,
+                    depth_stencil_attachment,
+                },
+            )?;
+            let context = PassContext {
+                name: pass.name,
+                data,
+                attachments: &self.attachments,
+            };
+            record(&context, &mut runner);
+        }
+        Ok(())
+    }
+}
+
+struct PassDependencies {
+    name: &'static str,
+    reads: Vec<&'static str>,
+    writes: Vec<&'static str>,
+}
+
+impl From<&GraphPass> for PassDependencies {
+    fn from(pass: &GraphPass) -> Self {
+        PassDependencies {
+            name: pass.name,
+            reads: pass.reads.clone(),
+            writes: pass.writes().collect(),
+        }
+    }
+}
+
+// An attachment can't be sampled while it's being rendered to in the same pass
+fn check_reads(pass: &PassDependencies) -> Result<(), RenderError> {
+    match pass.reads.iter().find(|read| pass.writes.contains(*read)) {
+        Some(name) => Err(RenderError::AttachmentReadWrite {
+            pass: pass.name.to_string(),
+            name: name.to_string(),
+        }),
+        None => Ok(()),
+    }
+}
+
+// Kahn's algorithm where passes without dependencies between them keep
+// the order they were added in. Every writer of an attachment runs before
+// its readers and multiple writers run in the order they were added. Passes
+// reading an attachment they write themselves are rejected by check_reads.
+fn sort_passes(passes: &[PassDependencies]) -> Result<Vec<usize>, RenderError> {
+    let mut edges: Vec<Vec<usize>> = vec![Vec::new(); passes.len()];
+    let mut in_degree = vec![0; passes.len()];
+    let mut add_edge = |from: usize, to: usize| {
+        if from != to && !edges[from].contains(&to) {
+            edges[from].push(to);
+            in_degree[to] += 1;
+        }
+    };
+    for (i, pass) in passes.iter().enumerate() {
+        for attachment in pass.writes.iter() {
+            for (j, other) in passes.iter().enumerate() {
+                if other.reads.contains(attachment) {
+                    add_edge(i, j);
+                }
+                if j > i && other.writes.contains(attachment) {
+                    add_edge(i, j);
+                }
+            }
+        }
+    }
+
+    let mut order = Vec::with_capacity(passes.len());
+    let mut visited = vec![false; passes.len()];
+    while let Some(next) = (0..passes.len()).find(|&i| !visited[i] && in_degree[i] == 0) {
+        visited[next] = true;
+        order.push(next);
+        for &to in edges[next].iter() {
+            in_degree[to] -= 1;
+        }
+    }
+    if order.len() != passes.len() {
+        return Err(RenderError::RenderGraphCycle {
+            passes: (0..passes.len())
+                .filter(|&i| !visited[i])
+                .map(|i| passes[i].name.to_string())
+                .collect(),
+        });
+    }
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn pass(
+        name: &'static str,
+        reads: &[&'static str],
+        writes: &[&'static str],
+    ) -> PassDependencies {
+        PassDependencies {
+            name,
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+        }
+    }
+
+    #[test]
+    fn dependency_order() -> Result<(), RenderError> {
+        let passes = [
+            pass("post_process", &["hdr"], &["swap_chain"]),
+            pass("main", &["shadow_map"], &["hdr", "depth"]),
+            pass("shadow", &[], &["shadow_map"]),
+            pass("ui", &[], &["swap_chain"]),
+        ];
+        assert_eq!(sort_passes(&passes)?, vec![2, 1, 0, 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn cycle_detection() {
+        let passes = [
+            pass("first", &["b"], &["a"]),
+            pass("second", &["a"], &["b"]),
+            pass("independent", &[], &["c"]),
+        ];
+        match sort_passes(&passes) {
+            Err(RenderError::RenderGraphCycle { passes }) => {
+                assert_eq!(passes, vec!["first".to_string(), "second".to_string()])
+            }
+            _ => panic!("Cycle wasn't detected"),
+        }
+    }
+
+    #[test]
+    fn self_dependency() {
+        let passes = [
+            pass("blur", &["bloom"], &["bloom"]),
+            pass("shadow", &[], &["shadow_map"]),
+            pass("composite", &["bloom", "shadow_map"], &["swap_chain"]),
+        ];
+        match check_reads(&passes[0]) {
+            Err(RenderError::AttachmentReadWrite { pass, name }) => {
+                assert_eq!(pass, "blur");
+                assert_eq!(name, "bloom");
+            }
+            _ => panic!("Reading a written attachment wasn't rejected"),
+        }
+        assert!(passes[1..].iter().all(|pass| check_reads(pass).is_ok()));
+    }
+}
@@ -1,4 +1,8 @@
+#[cfg(feature = "shaderc")]
+use crate::shader_variants::{ShaderDefines, ShaderVariants};
 use crate::{
+    index_buffer::{IndexBufferData, IndexType},
+    reflection,
     shader::{FragmentShader, VertexShader},
     uniforms::UniformBindGroup,
 };
@@ -11,6 +15,7 @@ use crate::{
 use smallvec::SmallVec;
 use std::{
     any::{type_name, TypeId},
+    marker::PhantomData,
     ops::{Deref, DerefMut},
     sync::Arc,
 };
@@ -22,13 +27,21 @@ pub struct RenderNode {
     local_uniform_bind_groups: Vec<UniformBindGroup>,
     vertex_buffer_types: SmallVec<[TypeId; VERTX_BUFFER_STACK_LIMIT]>,
     texture_types: Vec<TypeId>,
+    index_type: TypeId,
+    sample_count: u32,
+    color_formats: Vec<wgpu::TextureFormat>,
+    depth_format: Option<wgpu::TextureFormat>,
     pipeline: wgpu::RenderPipeline,
+    pipeline_state: PipelineState,
 }
 
 pub struct RenderNodeRunner<'a, 'b: 'a> {
     render_pass: wgpu::RenderPass<'a>,
     texture_types: &'b Vec<TypeId>,
+    shared_uniform_bind_groups: &'b [Arc<UniformBindGroup>],
+    local_uniform_bind_groups: &'b [UniformBindGroup],
     vertex_buffer_types: &'b SmallVec<[TypeId; VERTX_BUFFER_STACK_LIMIT]>,
+    index_type: TypeId,
 }
 
 impl<'a, 'b: 'a> RenderNodeRunner<'a, 'b> {
@@ -67,6 +80,44 @@ impl<'a, 'b: 'a> RenderNodeRunner<'a, 'b> {
         self.render_pass
             .set_vertex_buffer(index, data.get_gpu_buffer().slice(..));
     }
+
+    #[inline]
+    pub fn set_index_buffer_data<I: IndexType>(
+        &mut self,
+        data: &'b impl IndexBufferData<IndexType = I>,
+    ) {
+        assert!(
+            TypeId::of::<I>() == self.index_type,
+            format!(
+                "{}, doesn't match the index format of the RenderNode",
+                type_name::<I>(),
+            )
+        );
+        self.render_pass
+            .set_index_buffer(data.get_gpu_buffer().slice(..));
+    }
+
+    /// Selects which slot of every dynamic binding in the uniform bind group should be used,
+    /// `uniform_index` counts the shared bind groups first followed by the local ones.
+    #[inline]
+    pub fn set_dynamic_slots(
+        &mut self,
+        uniform_index: usize,
+        slots: &[u32],
+    ) -> Result<(), RenderError> {
+        let group = UniformBindGroup::find_dynamic_group(
+            self.shared_uniform_bind_groups,
+            self.local_uniform_bind_groups,
+            uniform_index,
+        )?;
+        let offsets = group.get_dynamic_offsets(slots)?;
+        self.render_pass.set_bind_group(
+            (self.texture_types.len() + uniform_index) as u32,
+            group.get_bind_group(),
+            &offsets,
+        );
+        Ok(())
+    }
 }
 
 impl<'a> Deref for RenderNodeRunner<'a, '_> {
@@ -82,11 +133,10 @@ impl<'a> DerefMut for RenderNodeRunner<'a, '_> {
     }
 }
 
-#[derive(Default)]
 pub struct RenderNodeBuilder<'a> {
     vertex_buffer_types: SmallVec<[TypeId; VERTX_BUFFER_STACK_LIMIT]>,
     vertex_buffer_descriptors:
-        SmallVec<[wgpu::VertexBufferDescriptor<'a>; VERTX_BUFFER_STACK_LIMIT]>,
+        SmallVec<[wgpu::VertexBufferDescriptor<'static>; VERTX_BUFFER_STACK_LIMIT]>,
     local_uniform_bind_groups: Vec<UniformBindGroup>,
     shared_uniform_bind_groups: Vec<Arc<UniformBindGroup>>,
     vertex_shader: Option<VertexShader>,
@@ -95,7 +145,44 @@ pub struct RenderNodeBuilder<'a> {
     depth_stencil_desc: Option<wgpu::DepthStencilStateDescriptor>,
     rasterization_state_desc: Option<wgpu::RasterizationStateDescriptor>,
     texture_types: Vec<TypeId>,
+    texture_layout_entries: Vec<&'static [wgpu::BindGroupLayoutEntry]>,
     texture_layout_generators: Vec<Box<dyn Fn(&wgpu::Device) -> &'static wgpu::BindGroupLayout>>,
+    index_format: wgpu::IndexFormat,
+    index_type: TypeId,
+    primitive_topology: wgpu::PrimitiveTopology,
+    sample_count: u32,
+    sample_mask: u32,
+    alpha_to_coverage_enabled: bool,
+    // The vertex buffer descriptors are kept by the node for shader reloading so
+    // they're always 'static, the lifetime only keeps the builder type unchanged
+    _lifetime: PhantomData<&'a ()>,
+}
+
+impl<'a> Default for RenderNodeBuilder<'a> {
+    fn default() -> Self {
+        RenderNodeBuilder {
+            vertex_buffer_types: SmallVec::new(),
+            vertex_buffer_descriptors: SmallVec::new(),
+            local_uniform_bind_groups: Vec::new(),
+            shared_uniform_bind_groups: Vec::new(),
+            vertex_shader: None,
+            fragment_shader: None,
+            color_states_desc: Vec::new(),
+            depth_stencil_desc: None,
+            rasterization_state_desc: None,
+            texture_types: Vec::new(),
+            texture_layout_entries: Vec::new(),
+            texture_layout_generators: Vec::new(),
+            // index data format defaults to u32
+            index_format: u32::FORMAT,
+            index_type: TypeId::of::<u32>(),
+            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+            sample_count: 1,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+            _lifetime: PhantomData,
+        }
+    }
 }
 
 impl<'a> RenderNodeBuilder<'a> {
@@ -105,6 +192,12 @@ impl<'a> RenderNodeBuilder<'a> {
         self
     }
 
+    pub fn set_index_format<I: IndexType>(mut self) -> Self {
+        self.index_format = I::FORMAT;
+        self.index_type = TypeId::of::<I>();
+        self
+    }
+
     pub fn add_local_uniform_bind_group(mut self, uniform: UniformBindGroup) -> Self {
         self.local_uniform_bind_groups.push(uniform);
         self
@@ -117,6 +210,7 @@ impl<'a> RenderNodeBuilder<'a> {
 
     pub fn add_texture<T: TextureShaderLayout>(mut self) -> Self {
         self.texture_types.push(TypeId::of::<T>());
+        self.texture_layout_entries.push(T::get_layout_entries());
         self.texture_layout_generators
             .push(Box::new(move |device: &wgpu::Device| T::get_layout(device)));
         self
@@ -147,6 +241,32 @@ impl<'a> RenderNodeBuilder<'a> {
         self
     }
 
+    /// Uses the variant of the vertex shader compiled with the given defines,
+    /// the variant is compiled if it isn't already cached.
+    #[cfg(feature = "shaderc")]
+    pub fn set_vertex_shader_variant(
+        mut self,
+        device: &wgpu::Device,
+        variants: &ShaderVariants<VertexShader>,
+        defines: &ShaderDefines,
+    ) -> Result<Self, RenderError> {
+        self.vertex_shader = Some(variants.get(device, defines)?);
+        Ok(self)
+    }
+
+    /// Uses the variant of the fragment shader compiled with the given defines,
+    /// the variant is compiled if it isn't already cached.
+    #[cfg(feature = "shaderc")]
+    pub fn set_fragment_shader_variant(
+        mut self,
+        device: &wgpu::Device,
+        variants: &ShaderVariants<FragmentShader>,
+        defines: &ShaderDefines,
+    ) -> Result<Self, RenderError> {
+        self.fragment_shader = Some(variants.get(device, defines)?);
+        Ok(self)
+    }
+
     pub fn set_default_rasterization_state(mut self) -> Self {
         self.rasterization_state_desc = Some(wgpu::RasterizationStateDescriptor {
             front_face: wgpu::FrontFace::Ccw,
@@ -181,7 +301,51 @@ impl<'a> RenderNodeBuilder<'a> {
         self
     }
 
-    fn construct_pipeline(&mut self, device: &wgpu::Device) -> wgpu::RenderPipeline {
+    pub fn set_primitive_topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.primitive_topology = topology;
+        self
+    }
+
+    /// Number of samples of the attachments the node renders to, any value above 1
+    /// enables MSAA and requires every color attachment to have a resolve target.
+    /// The count must be a power of two, otherwise build returns an error.
+    pub fn set_sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    pub fn set_sample_mask(mut self, sample_mask: u32) -> Self {
+        self.sample_mask = sample_mask;
+        self
+    }
+
+    pub fn set_alpha_to_coverage_enabled(mut self, enabled: bool) -> Self {
+        self.alpha_to_coverage_enabled = enabled;
+        self
+    }
+
+    pub fn build(self, device: &wgpu::Device) -> Result<RenderNode, RenderError> {
+        let vertex_shader = match self.vertex_shader {
+            Some(vertex_shader) => vertex_shader,
+            None => return Err(RenderError::MissingVertexShader),
+        };
+        if !self.sample_count.is_power_of_two() {
+            return Err(RenderError::InvalidSampleCount {
+                sample_count: self.sample_count,
+            });
+        }
+        let bind_group_entries = collect_bind_group_entries(
+            &self.texture_layout_entries,
+            &self.shared_uniform_bind_groups,
+            &self.local_uniform_bind_groups,
+        );
+        validate_shaders(
+            &vertex_shader,
+            self.fragment_shader.as_ref(),
+            &self.vertex_buffer_descriptors,
+            &bind_group_entries,
+        )?;
+
         let texture_layouts = self
             .texture_layout_generators
             .iter()
@@ -202,46 +366,138 @@ impl<'a> RenderNodeBuilder<'a> {
             .chain(local_bind_group_layouts)
             .collect::<Vec<&wgpu::BindGroupLayout>>();
 
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                bind_group_layouts: &bind_group_layouts,
-            });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &bind_group_layouts,
+        });
+
+        let color_formats = self
+            .color_states_desc
+            .iter()
+            .map(|color_state| color_state.format)
+            .collect();
+        let depth_format = self
+            .depth_stencil_desc
+            .as_ref()
+            .map(|depth_stencil| depth_stencil.format);
+
+        let pipeline_state = PipelineState {
+            layout,
+            vertex_shader,
+            fragment_shader: self.fragment_shader,
+            vertex_buffer_descriptors: self.vertex_buffer_descriptors,
+            texture_layout_entries: self.texture_layout_entries,
+            color_states_desc: self.color_states_desc,
+            depth_stencil_desc: self.depth_stencil_desc,
+            rasterization_state_desc: self.rasterization_state_desc,
+            index_format: self.index_format,
+            primitive_topology: self.primitive_topology,
+            sample_count: self.sample_count,
+            sample_mask: self.sample_mask,
+            alpha_to_coverage_enabled: self.alpha_to_coverage_enabled,
+        };
+        let pipeline = pipeline_state.construct_pipeline(device);
+        Ok(RenderNode {
+            shared_uniform_bind_groups: self.shared_uniform_bind_groups,
+            local_uniform_bind_groups: self.local_uniform_bind_groups,
+            pipeline,
+            pipeline_state,
+            texture_types: self.texture_types,
+            vertex_buffer_types: self.vertex_buffer_types,
+            index_type: self.index_type,
+            sample_count: self.sample_count,
+            color_formats,
+            depth_format,
+        })
+    }
+}
+
+// The bind group layout entries in the same order as they are set in the pipeline layout
+pub(crate) fn collect_bind_group_entries<'a>(
+    texture_layout_entries: &[&'a [wgpu::BindGroupLayoutEntry]],
+    shared_uniform_bind_groups: &'a [Arc<UniformBindGroup>],
+    local_uniform_bind_groups: &'a [UniformBindGroup],
+) -> Vec<&'a [wgpu::BindGroupLayoutEntry]> {
+    texture_layout_entries
+        .iter()
+        .copied()
+        .chain(
+            shared_uniform_bind_groups
+                .iter()
+                .map(|group| group.get_layout_entries()),
+        )
+        .chain(
+            local_uniform_bind_groups
+                .iter()
+                .map(UniformBindGroup::get_layout_entries),
+        )
+        .collect()
+}
 
+// Verifies that the entry points exist and that their interfaces match the bind groups
+// and vertex buffers
+fn validate_shaders(
+    vertex_shader: &VertexShader,
+    fragment_shader: Option<&FragmentShader>,
+    vertex_buffer_descriptors: &[wgpu::VertexBufferDescriptor],
+    bind_group_entries: &[&[wgpu::BindGroupLayoutEntry]],
+) -> Result<(), RenderError> {
+    let vertex_reflection = vertex_shader.get_entry_point_reflection()?;
+    reflection::validate_vertex_inputs(vertex_reflection, vertex_buffer_descriptors)?;
+    reflection::validate_bindings(
+        vertex_reflection,
+        wgpu::ShaderStage::VERTEX,
+        bind_group_entries,
+    )?;
+    if let Some(fragment_shader) = fragment_shader {
+        reflection::validate_bindings(
+            fragment_shader.get_entry_point_reflection()?,
+            wgpu::ShaderStage::FRAGMENT,
+            bind_group_entries,
+        )?;
+    }
+    Ok(())
+}
+
+// Everything needed to recreate the pipeline when the shaders are reloaded
+struct PipelineState {
+    layout: wgpu::PipelineLayout,
+    vertex_shader: VertexShader,
+    fragment_shader: Option<FragmentShader>,
+    vertex_buffer_descriptors:
+        SmallVec<[wgpu::VertexBufferDescriptor<'static>; VERTX_BUFFER_STACK_LIMIT]>,
+    texture_layout_entries: Vec<&'static [wgpu::BindGroupLayoutEntry]>,
+    color_states_desc: Vec<wgpu::ColorStateDescriptor>,
+    depth_stencil_desc: Option<wgpu::DepthStencilStateDescriptor>,
+    rasterization_state_desc: Option<wgpu::RasterizationStateDescriptor>,
+    index_format: wgpu::IndexFormat,
+    primitive_topology: wgpu::PrimitiveTopology,
+    sample_count: u32,
+    sample_mask: u32,
+    alpha_to_coverage_enabled: bool,
+}
+
+impl PipelineState {
+    fn construct_pipeline(&self, device: &wgpu::Device) -> wgpu::RenderPipeline {
         device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            layout: &render_pipeline_layout,
-            vertex_stage: self.vertex_shader.as_ref().unwrap().get_descriptor(),
+            layout: &self.layout,
+            vertex_stage: self.vertex_shader.get_descriptor(),
             fragment_stage: self
                 .fragment_shader
                 .as_ref()
                 .map(FragmentShader::get_descriptor),
-            rasterization_state: std::mem::replace(&mut self.rasterization_state_desc, None),
-            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+            rasterization_state: self.rasterization_state_desc.clone(),
+            primitive_topology: self.primitive_topology,
             color_states: &self.color_states_desc,
-            depth_stencil_state: std::mem::replace(&mut self.depth_stencil_desc, None),
+            depth_stencil_state: self.depth_stencil_desc.clone(),
             vertex_state: wgpu::VertexStateDescriptor {
-                index_format: wgpu::IndexFormat::Uint32,
+                index_format: self.index_format,
                 vertex_buffers: &self.vertex_buffer_descriptors,
             },
-            sample_count: 1,
-            sample_mask: !0,
-            alpha_to_coverage_enabled: false,
+            sample_count: self.sample_count,
+            sample_mask: self.sample_mask,
+            alpha_to_coverage_enabled: self.alpha_to_coverage_enabled,
         })
     }
-
-    pub fn build(mut self, device: &wgpu::Device) -> Result<RenderNode, RenderError> {
-        if self.vertex_shader.is_none() {
-            Err(RenderError::MissingVertexShader)
-        } else {
-            let pipeline = self.construct_pipeline(device);
-            Ok(RenderNode {
-                shared_uniform_bind_groups: self.shared_uniform_bind_groups,
-                local_uniform_bind_groups: self.local_uniform_bind_groups,
-                pipeline,
-                texture_types: self.texture_types,
-                vertex_buffer_types: self.vertex_buffer_types,
-            })
-        }
-    }
 }
 
 impl RenderNode {
@@ -249,6 +505,74 @@ impl RenderNode {
         RenderNodeBuilder::default()
     }
 
+    #[inline]
+    pub fn get_sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    #[inline]
+    pub fn get_color_formats(&self) -> &[wgpu::TextureFormat] {
+        &self.color_formats
+    }
+
+    #[inline]
+    pub fn get_depth_format(&self) -> Option<wgpu::TextureFormat> {
+        self.depth_format
+    }
+
+    /// Recompiles the shaders whose source files changed since they were compiled and rebuilds
+    /// the pipeline in place, the bind groups are kept as they are. Returns true if the
+    /// pipeline was rebuilt. If a shader fails to compile or no longer matches the node
+    /// the old pipeline is kept and the error is returned, the changed shaders are compiled
+    /// again on the next call so no changes are lost.
+    #[cfg(any(feature = "shaderc", feature = "wgsl"))]
+    pub fn reload_shaders(&mut self, device: &wgpu::Device) -> Result<bool, RenderError> {
+        let state = &mut self.pipeline_state;
+        // The replaced shaders record the new modification times, the old ones are
+        // only replaced once the whole pipeline could be rebuilt
+        let vertex_changed = state.vertex_shader.has_changed();
+        let fragment_changed = state
+            .fragment_shader
+            .as_ref()
+            .map_or(false, FragmentShader::has_changed);
+        if !vertex_changed && !fragment_changed {
+            return Ok(false);
+        }
+        let vertex_shader = if vertex_changed {
+            state.vertex_shader.recompile(device).transpose()
+        } else {
+            Ok(None)
+        };
+        let fragment_shader = match state.fragment_shader.as_ref() {
+            Some(fragment_shader) if fragment_changed => {
+                fragment_shader.recompile(device).transpose()
+            }
+            _ => Ok(None),
+        };
+        let (vertex_shader, fragment_shader) = (vertex_shader?, fragment_shader?);
+        let bind_group_entries = collect_bind_group_entries(
+            &state.texture_layout_entries,
+            &self.shared_uniform_bind_groups,
+            &self.local_uniform_bind_groups,
+        );
+        validate_shaders(
+            vertex_shader.as_ref().unwrap_or(&state.vertex_shader),
+            fragment_shader
+                .as_ref()
+                .or_else(|| state.fragment_shader.as_ref()),
+            &state.vertex_buffer_descriptors,
+            &bind_group_entries,
+        )?;
+        if let Some(vertex_shader) = vertex_shader {
+            state.vertex_shader = vertex_shader;
+        }
+        if fragment_shader.is_some() {
+            state.fragment_shader = fragment_shader;
+        }
+        self.pipeline = state.construct_pipeline(device);
+        Ok(true)
+    }
+
     #[inline]
     pub fn update(
         &self,
@@ -264,13 +588,111 @@ impl RenderNode {
         )
     }
 
+    // The formats and sample counts of the attachment views can't be queried
+    // so only the shape of the descriptor is checked here, see try_runner_with_formats
+    fn validate_render_pass(
+        &self,
+        render_pass_descriptor: &wgpu::RenderPassDescriptor,
+    ) -> Result<(), RenderError> {
+        let color_attachments = render_pass_descriptor.color_attachments;
+        self.validate_color_count(color_attachments.len())?;
+        for (i, color_attachment) in color_attachments.iter().enumerate() {
+            if self.sample_count > 1 && color_attachment.resolve_target.is_none() {
+                return Err(RenderError::MissingResolveTarget {
+                    name: format!("color {}", i),
+                });
+            } else if self.sample_count == 1 && color_attachment.resolve_target.is_some() {
+                return Err(RenderError::RenderPassMismatch {
+                    attachment: format!("color {}", i),
+                    reason: "it has a resolve target but the RenderNode isn't multisampled"
+                        .to_string(),
+                });
+            }
+        }
+        match (
+            self.depth_format,
+            render_pass_descriptor.depth_stencil_attachment.as_ref(),
+        ) {
+            (Some(format), None) => Err(RenderError::RenderPassMismatch {
+                attachment: "depth stencil".to_string(),
+                reason: format!("the RenderNode expects a {:?} attachment", format),
+            }),
+            (None, Some(_)) => Err(RenderError::RenderPassMismatch {
+                attachment: "depth stencil".to_string(),
+                reason: "the RenderNode doesn't have a depth stencil state".to_string(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn validate_color_count(&self, count: usize) -> Result<(), RenderError> {
+        if count != self.color_formats.len() {
+            return Err(RenderError::RenderPassMismatch {
+                attachment: format!("color {}", count.min(self.color_formats.len())),
+                reason: format!(
+                    "the RenderNode has {} color targets but {} color attachments were given",
+                    self.color_formats.len(),
+                    count
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    fn validate_attachment_formats(
+        &self,
+        color_formats: &[wgpu::TextureFormat],
+        depth_format: Option<wgpu::TextureFormat>,
+        sample_count: u32,
+    ) -> Result<(), RenderError> {
+        self.validate_color_count(color_formats.len())?;
+        if sample_count != self.sample_count {
+            let name = if color_formats.is_empty() {
+                "depth stencil".to_string()
+            } else {
+                "color 0".to_string()
+            };
+            return Err(RenderError::AttachmentSampleCountMismatch {
+                name,
+                attachment_samples: sample_count,
+                expected: self.sample_count,
+            });
+        }
+        let color_mismatch = color_formats
+            .iter()
+            .zip(self.color_formats.iter())
+            .enumerate()
+            .find(|(_, (given, expected))| given != expected);
+        if let Some((i, (given, expected))) = color_mismatch {
+            return Err(RenderError::RenderPassMismatch {
+                attachment: format!("color {}", i),
+                reason: format!(
+                    "the RenderNode expects {:?} but the attachment is {:?}",
+                    expected, given
+                ),
+            });
+        }
+        match (self.depth_format, depth_format) {
+            (Some(expected), Some(given)) if expected != given => {
+                Err(RenderError::RenderPassMismatch {
+                    attachment: "depth stencil".to_string(),
+                    reason: format!(
+                        "the RenderNode expects {:?} but the attachment is {:?}",
+                        expected, given
+                    ),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Begins a render pass with the pipeline and uniform bind groups of the node set.
+    /// The RenderPassDescriptor isn't checked against the node, see try_runner.
     pub fn runner<'a: 'b, 'b>(
         &'a self,
         command_encoder: &'b mut wgpu::CommandEncoder,
         render_pass_descriptor: wgpu::RenderPassDescriptor<'b, '_>,
     ) -> RenderNodeRunner<'a, 'b> {
-        // TODO: more sanity checks can be added here between the given state descriptors
-        // in the builder and the given RenderPassDescriptor
         let mut render_pass = command_encoder.begin_render_pass(&render_pass_descriptor);
         render_pass.set_pipeline(&self.pipeline);
         let local_iter = self.local_uniform_bind_groups.iter();
@@ -283,14 +705,305 @@ impl RenderNode {
                 render_pass.set_bind_group(
                     (self.texture_types.len() + i) as u32,
                     group.get_bind_group(),
-                    &[],
+                    &group.get_default_dynamic_offsets(),
                 );
             });
 
         RenderNodeRunner {
             render_pass,
             texture_types: &self.texture_types,
+            shared_uniform_bind_groups: &self.shared_uniform_bind_groups,
+            local_uniform_bind_groups: &self.local_uniform_bind_groups,
             vertex_buffer_types: &self.vertex_buffer_types,
+            index_type: self.index_type,
+        }
+    }
+
+    /// Same as runner but returns an error if the number of color attachments, their resolve
+    /// targets or the depth stencil attachment don't match the node. The formats and sample
+    /// counts of the views can't be queried so they're only checked by try_runner_with_formats.
+    pub fn try_runner<'a: 'b, 'b>(
+        &'a self,
+        command_encoder: &'b mut wgpu::CommandEncoder,
+        render_pass_descriptor: wgpu::RenderPassDescriptor<'b, '_>,
+    ) -> Result<RenderNodeRunner<'a, 'b>, RenderError> {
+        self.validate_render_pass(&render_pass_descriptor)?;
+        Ok(self.runner(command_encoder, render_pass_descriptor))
+    }
+
+    /// Same as try_runner but also checks the formats of the attachments, given in the same
+    /// order as the color attachments of the descriptor, and the sample count of the color and
+    /// depth stencil attachments against the node. Resolve targets are always single sampled.
+    pub fn try_runner_with_formats<'a: 'b, 'b>(
+        &'a self,
+        command_encoder: &'b mut wgpu::CommandEncoder,
+        render_pass_descriptor: wgpu::RenderPassDescriptor<'b, '_>,
+        color_formats: &[wgpu::TextureFormat],
+        depth_format: Option<wgpu::TextureFormat>,
+        sample_count: u32,
+    ) -> Result<RenderNodeRunner<'a, 'b>, RenderError> {
+        self.validate_attachment_formats(color_formats, depth_format, sample_count)?;
+        self.try_runner(command_encoder, render_pass_descriptor)
+    }
+}
+
+#[cfg(all(test, feature = "shaderc"))]
+mod tests {
+
+    use super::*;
+    use crate::{
+        shader::ShaderCompileOptions,
+        test_utils::{create_test_env, test_dir, write_file},
+    };
+
+    const VERTEX_SHADER: &str = "#version 450\n\
+                                 void main() { gl_Position = vec4(0.0, 0.0, 0.0, 1.0); }\n";
+    const FRAGMENT_SHADER: &str = "#version 450\n\
+                                   layout(location = 0) out vec4 f_color;\n\
+                                   void main() { f_color = vec4(1.0); }\n";
+
+    #[test]
+    fn hot_reload() -> Result<(), RenderError> {
+        let (device, _) = create_test_env();
+        let dir = test_dir("hot_reload");
+        let vertex_path = dir.join("reload.vs");
+        let fragment_path = dir.join("reload.fs");
+        write_file(&vertex_path, VERTEX_SHADER);
+        write_file(&fragment_path, FRAGMENT_SHADER);
+        let mut node = RenderNode::builder()
+            .set_vertex_shader(VertexShader::new(&device, &vertex_path)?)
+            .set_fragment_shader(FragmentShader::new(&device, &fragment_path)?)
+            .add_default_color_state_desc(wgpu::TextureFormat::Bgra8UnormSrgb)
+            .build(&device)?;
+        assert!(!node.reload_shaders(&device)?);
+
+        write_file(
+            &fragment_path,
+            &FRAGMENT_SHADER.replace("vec4(1.0)", "vec4(0.5)"),
+        );
+        assert!(node.reload_shaders(&device)?);
+        assert!(!node.reload_shaders(&device)?);
+
+        // A broken vertex shader must not drop the pending fragment change
+        write_file(
+            &vertex_path,
+            "#version 450\nvoid main() { gl_Position = ; }\n",
+        );
+        write_file(&fragment_path, FRAGMENT_SHADER);
+        match node.reload_shaders(&device) {
+            Err(RenderError::ShaderCompileError { path, .. }) => assert_eq!(path, vertex_path),
+            result => panic!("Expected a compile error but got {:?}", result.map(|_| ())),
+        }
+        // The changes are still pending after the error
+        assert!(node.reload_shaders(&device).is_err());
+
+        // Shaders that compile but don't match the node are rejected too
+        write_file(
+            &vertex_path,
+            &VERTEX_SHADER.replace("void main", "layout(location = 0) in vec3 pos;\nvoid main"),
+        );
+        match node.reload_shaders(&device) {
+            Err(RenderError::VertexInputMismatch { location: 0, .. }) => {}
+            result => panic!(
+                "Expected a vertex input error but got {:?}",
+                result.map(|_| ())
+            ),
         }
+
+        write_file(&vertex_path, VERTEX_SHADER);
+        assert!(node.reload_shaders(&device)?);
+        assert!(!node.reload_shaders(&device)?);
+        Ok(())
+    }
+
+    #[test]
+    fn attachment_formats() -> Result<(), RenderError> {
+        let (device, _) = create_test_env();
+        let options = ShaderCompileOptions::default();
+        let node = RenderNode::builder()
+            .set_vertex_shader(VertexShader::from_glsl(
+                &device,
+                "formats.vs",
+                VERTEX_SHADER,
+                &options,
+            )?)
+            .set_fragment_shader(FragmentShader::from_glsl(
+                &device,
+                "formats.fs",
+                FRAGMENT_SHADER,
+                &options,
+            )?)
+            .add_default_color_state_desc(wgpu::TextureFormat::Bgra8UnormSrgb)
+            .set_default_depth_stencil_state()
+            .build(&device)?;
+        assert!(node
+            .validate_attachment_formats(
+                &[wgpu::TextureFormat::Bgra8UnormSrgb],
+                Some(wgpu::TextureFormat::Depth32Float),
+                1
+            )
+            .is_ok());
+        match node.validate_attachment_formats(&[wgpu::TextureFormat::Rgba8Unorm], None, 1) {
+            Err(RenderError::RenderPassMismatch { attachment, .. }) => {
+                assert_eq!(attachment, "color 0")
+            }
+            result => panic!("Expected a format mismatch but got {:?}", result),
+        }
+        match node.validate_attachment_formats(
+            &[wgpu::TextureFormat::Bgra8UnormSrgb],
+            Some(wgpu::TextureFormat::Depth24Plus),
+            1,
+        ) {
+            Err(RenderError::RenderPassMismatch { attachment, .. }) => {
+                assert_eq!(attachment, "depth stencil")
+            }
+            result => panic!("Expected a format mismatch but got {:?}", result),
+        }
+        for color_formats in &[&[][..], &[wgpu::TextureFormat::Bgra8UnormSrgb; 2][..]] {
+            match node.validate_attachment_formats(color_formats, None, 1) {
+                Err(RenderError::RenderPassMismatch { attachment, .. }) => {
+                    assert_eq!(attachment, format!("color {}", color_formats.len().min(1)))
+                }
+                result => panic!("Expected a color count mismatch but got {:?}", result),
+            }
+        }
+        match node.validate_attachment_formats(&[wgpu::TextureFormat::Bgra8UnormSrgb], None, 4) {
+            Err(RenderError::AttachmentSampleCountMismatch {
+                name,
+                attachment_samples: 4,
+                expected: 1,
+            }) => assert_eq!(name, "color 0"),
+            result => panic!("Expected a sample count mismatch but got {:?}", result),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn render_pass_descriptor() -> Result<(), RenderError> {
+        let (device, _) = create_test_env();
+        let options = ShaderCompileOptions::default();
+        let node = |sample_count, depth| -> Result<RenderNode, RenderError> {
+            let builder = RenderNode::builder()
+                .set_vertex_shader(VertexShader::from_glsl(
+                    &device,
+                    "descriptor.vs",
+                    VERTEX_SHADER,
+                    &options,
+                )?)
+                .add_default_color_state_desc(wgpu::TextureFormat::Bgra8UnormSrgb)
+                .set_sample_count(sample_count);
+            if depth {
+                builder.set_default_depth_stencil_state().build(&device)
+            } else {
+                builder.build(&device)
+            }
+        };
+        // Nothing is recorded so the sample counts and formats of the views don't matter
+        let view = |format| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: None,
+                    size: wgpu::Extent3d {
+                        width: 4,
+                        height: 4,
+                        depth: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+                })
+                .create_default_view()
+        };
+        let color = view(wgpu::TextureFormat::Bgra8UnormSrgb);
+        let resolve_target = view(wgpu::TextureFormat::Bgra8UnormSrgb);
+        let depth = view(wgpu::TextureFormat::Depth32Float);
+        let validate =
+            |node: &RenderNode,
+             resolve_target: Option<&wgpu::TextureView>,
+             depth_stencil_attachment: Option<&wgpu::TextureView>| {
+                let color_attachments = [wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment: &color,
+                    resolve_target,
+                    ops: wgpu::Operations::default(),
+                }];
+                node.validate_render_pass(&wgpu::RenderPassDescriptor {
+                    color_attachments: &color_attachments,
+                    depth_stencil_attachment: depth_stencil_attachment.map(|attachment| {
+                        wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                            attachment,
+                            depth_ops: Some(wgpu::Operations::default()),
+                            stencil_ops: None,
+                        }
+                    }),
+                })
+            };
+
+        let multisampled = node(4, false)?;
+        assert!(validate(&multisampled, Some(&resolve_target), None).is_ok());
+        match validate(&multisampled, None, None) {
+            Err(RenderError::MissingResolveTarget { name }) => assert_eq!(name, "color 0"),
+            result => panic!("Expected a missing resolve target but got {:?}", result),
+        }
+        match validate(&node(1, false)?, Some(&resolve_target), None) {
+            Err(RenderError::RenderPassMismatch { attachment, .. }) => {
+                assert_eq!(attachment, "color 0")
+            }
+            result => panic!("Expected a resolve target mismatch but got {:?}", result),
+        }
+
+        let with_depth = node(1, true)?;
+        assert!(validate(&with_depth, None, Some(&depth)).is_ok());
+        match validate(&with_depth, None, None) {
+            Err(RenderError::RenderPassMismatch { attachment, .. }) => {
+                assert_eq!(attachment, "depth stencil")
+            }
+            result => panic!("Expected a depth stencil mismatch but got {:?}", result),
+        }
+        match validate(&node(1, false)?, None, Some(&depth)) {
+            Err(RenderError::RenderPassMismatch { attachment, .. }) => {
+                assert_eq!(attachment, "depth stencil")
+            }
+            result => panic!("Expected a depth stencil mismatch but got {:?}", result),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn sample_count() -> Result<(), RenderError> {
+        let (device, _) = create_test_env();
+        let options = ShaderCompileOptions::default();
+        let builder = || -> Result<RenderNodeBuilder<'static>, RenderError> {
+            Ok(RenderNode::builder()
+                .set_vertex_shader(VertexShader::from_glsl(
+                    &device,
+                    "samples.vs",
+                    VERTEX_SHADER,
+                    &options,
+                )?)
+                .add_default_color_state_desc(wgpu::TextureFormat::Bgra8UnormSrgb))
+        };
+        for &sample_count in &[0, 3, 6] {
+            match builder()?.set_sample_count(sample_count).build(&device) {
+                Err(RenderError::InvalidSampleCount {
+                    sample_count: count,
+                }) => {
+                    assert_eq!(count, sample_count)
+                }
+                result => panic!(
+                    "Expected a sample count error but got {:?}",
+                    result.map(|_| ())
+                ),
+            }
+        }
+        assert_eq!(
+            builder()?
+                .set_sample_count(4)
+                .build(&device)?
+                .get_sample_count(),
+            4
+        );
+        Ok(())
     }
 }
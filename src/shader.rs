@@ -1,68 +1,762 @@
-use crate::RenderError;
-use shaderc::{CompilationArtifact, CompileOptions, Compiler, ShaderKind};
-use std::fs::File;
+#[cfg(feature = "shaderc")]
+use crate::shader_cache;
+#[cfg(feature = "shaderc")]
+use crate::shader_diagnostics::parse_glslang_output;
+#[cfg(any(feature = "shaderc", feature = "wgsl"))]
+use crate::shader_diagnostics::DiagnosticSeverity;
+use crate::{
+    reflection::{ReflectedEntryPoint, ShaderReflection},
+    shader_config::ShaderCompileConfig,
+    shader_diagnostics::ShaderDiagnostic,
+    RenderError,
+};
+#[cfg(feature = "shaderc")]
+use shaderc::{
+    CompileOptions, Compiler, IncludeCallbackResult, IncludeType, ResolvedInclude, ShaderKind,
+};
+#[cfg(feature = "shaderc")]
 use std::{
+    cell::RefCell,
+    fs::File,
+    hash::{Hash, Hasher},
     io::Read,
+};
+use std::{
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 use wgpu::ShaderModuleSource;
 
-fn compile_glsl(
+/// The language of a shader source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShaderLanguage {
+    Glsl,
+    Wgsl,
+}
+
+impl ShaderLanguage {
+    /// Files with a .wgsl extension are WGSL, everything else is treated as GLSL
+    pub fn from_path(path: impl AsRef<Path>) -> ShaderLanguage {
+        match path.as_ref().extension() {
+            Some(extension) if extension == "wgsl" => ShaderLanguage::Wgsl,
+            _ => ShaderLanguage::Glsl,
+        }
+    }
+}
+
+/// Options used when compiling GLSL shaders. Includes with quotes are first resolved
+/// relative to the including file and then in the include directories, includes
+/// with angle brackets are only resolved in the include directories.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(not(feature = "shaderc"), allow(dead_code))]
+pub struct ShaderCompileOptions {
+    include_dirs: Vec<PathBuf>,
+    macro_definitions: Vec<(String, Option<String>)>,
+    cache_dir: Option<PathBuf>,
+    config: Option<ShaderCompileConfig>,
+}
+
+impl ShaderCompileOptions {
+    pub fn new() -> Self {
+        ShaderCompileOptions::default()
+    }
+
+    pub fn add_include_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.include_dirs.push(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Equivalent to #define name value at the top of the shader
+    pub fn add_macro_definition(mut self, name: &str, value: Option<&str>) -> Self {
+        self.macro_definitions
+            .push((name.to_string(), value.map(ToString::to_string)));
+        self
+    }
+
+    /// Stores the compiled SPIR-V in the directory and reuses it as long as the source,
+    /// the included files, the defines and the shader kind stay the same.
+    /// The cache can be emptied with clear_shader_cache. Failing to write to the
+    /// directory is ignored and only means the shader is compiled again next time.
+    pub fn set_cache_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.cache_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Overrides the global ShaderCompileConfig for the shaders compiled with these options
+    pub fn set_config(mut self, config: ShaderCompileConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    // The config of the options or the global one if it isn't set
+    pub(crate) fn get_config(&self) -> ShaderCompileConfig {
+        self.config.unwrap_or_else(ShaderCompileConfig::global)
+    }
+
+    // Hashes every option that affects the compiled output
+    #[cfg(feature = "shaderc")]
+    pub(crate) fn hash_inputs(&self, hasher: &mut impl Hasher) {
+        self.include_dirs.hash(hasher);
+        self.macro_definitions.hash(hasher);
+        self.get_config().hash(hasher);
+    }
+}
+
+#[cfg(feature = "shaderc")]
+fn resolve_include(
+    requested_source: &str,
+    include_type: IncludeType,
+    requesting_source: &str,
+    include_dirs: &[PathBuf],
+) -> IncludeCallbackResult {
+    let relative_dir = match include_type {
+        IncludeType::Relative => Path::new(requesting_source).parent(),
+        IncludeType::Standard => None,
+    };
+    let path = relative_dir
+        .into_iter()
+        .chain(include_dirs.iter().map(PathBuf::as_path))
+        .map(|dir| dir.join(requested_source))
+        .find(|path| path.is_file())
+        .ok_or_else(|| {
+            format!(
+                "Couldn't find {} included from {}",
+                requested_source, requesting_source
+            )
+        })?;
+    let content = std::fs::read_to_string(&path)
+        .map_err(|err| format!("Couldn't read {}: {}", path.display(), err))?;
+    Ok(ResolvedInclude {
+        resolved_name: path.to_string_lossy().into_owned(),
+        content,
+    })
+}
+
+// SPIR-V together with what the compiler reported while producing it
+#[cfg(any(feature = "shaderc", feature = "wgsl"))]
+pub(crate) struct CompiledSpirV {
+    pub(crate) spirv: Vec<u32>,
+    // Paths of every file that was included during compilation
+    pub(crate) included_files: Vec<PathBuf>,
+    pub(crate) warnings: Vec<ShaderDiagnostic>,
+}
+
+// A compile error consisting of a single diagnostic
+#[cfg(any(feature = "shaderc", feature = "wgsl"))]
+fn compile_error(file: &Path, location: Option<(u32, u32)>, message: String) -> RenderError {
+    RenderError::ShaderCompileError {
+        diagnostics: vec![ShaderDiagnostic {
+            file: file.to_path_buf(),
+            line: location.map(|(line, _)| line),
+            column: location.map(|(_, column)| column),
+            severity: DiagnosticSeverity::Error,
+            message,
+        }],
+        path: file.to_path_buf(),
+    }
+}
+
+/// Compiles the GLSL file at path.
+#[cfg(feature = "shaderc")]
+pub(crate) fn compile_glsl(
     path: impl AsRef<Path>,
     shader_type: ShaderKind,
-) -> Result<CompilationArtifact, RenderError> {
+    compile_options: &ShaderCompileOptions,
+) -> Result<CompiledSpirV, RenderError> {
     let mut file = File::open(&path)?;
     let mut src = String::new();
-    let mut compiler = Compiler::new().expect("Can't create shader compiler");
-    let options = CompileOptions::new().expect("Can't create compiler options");
     file.read_to_string(&mut src)?;
-    compiler
-        .compile_into_spirv(&src, shader_type, "test.glsl", "main", Some(&options))
-        .map_err(|err| RenderError::ShaderCompileError {
-            compile_error: err.to_string(),
-            path: PathBuf::from(path.as_ref()),
-        })
+    compile_glsl_source(
+        &src,
+        &path.as_ref().to_string_lossy(),
+        shader_type,
+        compile_options,
+    )
+}
+
+// The name is reported as the file name in errors and quoted
+// includes are resolved relative to it
+#[cfg(feature = "shaderc")]
+pub(crate) fn compile_glsl_source(
+    src: &str,
+    name: &str,
+    shader_type: ShaderKind,
+    compile_options: &ShaderCompileOptions,
+) -> Result<CompiledSpirV, RenderError> {
+    let cache_dir = match compile_options.cache_dir.as_ref() {
+        Some(cache_dir) => cache_dir,
+        None => return compile_glsl_uncached(src, name, shader_type, compile_options),
+    };
+    let key = shader_cache::cache_key(src, name, shader_type, compile_options);
+    if let Some(cached) = shader_cache::load(cache_dir, key) {
+        return Ok(cached);
+    }
+    let compiled = compile_glsl_uncached(src, name, shader_type, compile_options)?;
+    // The cache is only an optimization, a read only or full disk
+    // shouldn't fail a compilation that already succeeded
+    let _ = shader_cache::store(cache_dir, key, &compiled);
+    Ok(compiled)
+}
+
+#[cfg(feature = "shaderc")]
+fn compile_glsl_uncached(
+    src: &str,
+    name: &str,
+    shader_type: ShaderKind,
+    compile_options: &ShaderCompileOptions,
+) -> Result<CompiledSpirV, RenderError> {
+    let mut compiler = Compiler::new().expect("Can't create shader compiler");
+    let mut options = CompileOptions::new().expect("Can't create compiler options");
+    compile_options.get_config().apply(&mut options);
+    for (name, value) in compile_options.macro_definitions.iter() {
+        options.add_macro_definition(name, value.as_deref());
+    }
+    let included_files = RefCell::new(Vec::new());
+    options.set_include_callback(
+        |requested_source, include_type, requesting_source, _include_depth| {
+            let resolved = resolve_include(
+                requested_source,
+                include_type,
+                requesting_source,
+                &compile_options.include_dirs,
+            )?;
+            included_files
+                .borrow_mut()
+                .push(PathBuf::from(&resolved.resolved_name));
+            Ok(resolved)
+        },
+    );
+    // Errors in included files are reported with the resolved path and line
+    // of the included file by shaderc. GLSL only has a single main entry point,
+    // selecting any other name fails with MissingEntryPoint through reflection.
+    let artifact = compiler
+        .compile_into_spirv(src, shader_type, name, DEFAULT_ENTRY_POINT, Some(&options))
+        .map_err(|err| match err {
+            shaderc::Error::CompilationError(_, output) => RenderError::ShaderCompileError {
+                diagnostics: parse_glslang_output(&output, Path::new(name)),
+                path: PathBuf::from(name),
+            },
+            err => compile_error(Path::new(name), None, err.to_string()),
+        })?;
+    drop(options);
+    Ok(CompiledSpirV {
+        spirv: artifact.as_binary().to_vec(),
+        included_files: included_files.into_inner(),
+        warnings: parse_glslang_output(&artifact.get_warning_messages(), Path::new(name)),
+    })
+}
+
+/// Compiles WGSL to SPIR-V, the name is only used in error messages.
+#[cfg(feature = "wgsl")]
+pub(crate) fn compile_wgsl(
+    src: &str,
+    name: &str,
+    config: &ShaderCompileConfig,
+) -> Result<CompiledSpirV, RenderError> {
+    let wgsl_error = |message: String, location: Option<naga::SourceLocation>| {
+        let location = location.map(|location| (location.line_number, location.line_position));
+        compile_error(Path::new(name), location, message)
+    };
+    let module = naga::front::wgsl::parse_str(src)
+        .map_err(|err| wgsl_error(err.message().to_string(), err.location(src)))?;
+    let info = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .map_err(|err| wgsl_error(error_chain(&err), err.location(src)))?;
+    let mut options = naga::back::spv::Options::default();
+    // wgpu already expects SPIR-V shaders to use the WGSL coordinate space
+    options
+        .flags
+        .remove(naga::back::spv::WriterFlags::ADJUST_COORDINATE_SPACE);
+    options
+        .flags
+        .set(naga::back::spv::WriterFlags::DEBUG, config.get_debug_info());
+    let spirv = naga::back::spv::write_vec(&module, &info, &options, None)
+        .map_err(|err| wgsl_error(err.to_string(), None))?;
+    // naga doesn't report any warnings
+    Ok(CompiledSpirV {
+        spirv,
+        included_files: Vec::new(),
+        warnings: Vec::new(),
+    })
+}
+
+// Validation errors only describe the outermost failure, the cause is in the sources
+#[cfg(feature = "wgsl")]
+fn error_chain(err: &dyn std::error::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+// Compiles the file as the given language, the stage is only needed for GLSL since WGSL entry points declare their own stage.
+#[cfg(any(feature = "shaderc", feature = "wgsl"))]
+#[cfg_attr(not(feature = "shaderc"), allow(unused_variables))]
+fn compile_file(
+    path: &Path,
+    language: ShaderLanguage,
+    stage: wgpu::ShaderStage,
+    options: &ShaderCompileOptions,
+) -> Result<CompiledSpirV, RenderError> {
+    match language {
+        #[cfg(feature = "shaderc")]
+        ShaderLanguage::Glsl => compile_glsl(path, shader_kind(stage), options),
+        #[cfg(feature = "wgsl")]
+        ShaderLanguage::Wgsl => {
+            let src = std::fs::read_to_string(path)?;
+            compile_wgsl(&src, &path.to_string_lossy(), &options.get_config())
+        }
+        #[allow(unreachable_patterns)]
+        _ => Err(compile_error(
+            path,
+            None,
+            format!(
+                "{:?} shaders can't be compiled since the {} feature is disabled",
+                language,
+                match language {
+                    ShaderLanguage::Glsl => "shaderc",
+                    ShaderLanguage::Wgsl => "wgsl",
+                }
+            ),
+        )),
+    }
+}
+
+#[cfg(feature = "shaderc")]
+fn shader_kind(stage: wgpu::ShaderStage) -> ShaderKind {
+    if stage == wgpu::ShaderStage::VERTEX {
+        ShaderKind::Vertex
+    } else if stage == wgpu::ShaderStage::FRAGMENT {
+        ShaderKind::Fragment
+    } else {
+        ShaderKind::Compute
+    }
+}
+
+// Converts SPIR-V bytes of either endianness to words
+fn spirv_words(bytes: &[u8]) -> Result<Vec<u32>, RenderError> {
+    if bytes.len() % 4 != 0 {
+        return Err(RenderError::InvalidSpirV {
+            reason: format!("the length {} isn't a multiple of 4", bytes.len()),
+        });
+    }
+    let words = bytes
+        .chunks_exact(4)
+        .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect::<Vec<u32>>();
+    match words.first() {
+        Some(&SPIRV_MAGIC) => Ok(words),
+        Some(&magic) if magic == SPIRV_MAGIC.swap_bytes() => {
+            Ok(words.into_iter().map(u32::swap_bytes).collect())
+        }
+        _ => Err(RenderError::InvalidSpirV {
+            reason: "missing the SPIR-V magic number".to_string(),
+        }),
+    }
+}
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+// Keeps track of the shader source file and its includes so
+// it can be recompiled when any of them change
+#[cfg_attr(not(any(feature = "shaderc", feature = "wgsl")), allow(dead_code))]
+#[derive(Clone)]
+pub(crate) struct ShaderSource {
+    path: PathBuf,
+    language: ShaderLanguage,
+    options: ShaderCompileOptions,
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl ShaderSource {
+    #[cfg(any(feature = "shaderc", feature = "wgsl"))]
+    pub(crate) fn new(
+        path: &Path,
+        language: ShaderLanguage,
+        options: &ShaderCompileOptions,
+        included_files: Vec<PathBuf>,
+    ) -> Self {
+        let files = std::iter::once(path.to_path_buf())
+            .chain(included_files)
+            .map(|file| {
+                let modified = modified_time(&file);
+                (file, modified)
+            })
+            .collect();
+        ShaderSource {
+            path: path.to_path_buf(),
+            language,
+            options: options.clone(),
+            files,
+        }
+    }
+
+    // True if any of the files were modified since they were compiled or last polled
+    pub(crate) fn has_changed(&self) -> bool {
+        self.files
+            .iter()
+            .any(|(file, last_modified)| modified_time(file) != *last_modified)
+    }
+
+    // Returns true once per modification of the files
+    fn poll_changed(&mut self) -> bool {
+        let mut changed = false;
+        for (file, last_modified) in self.files.iter_mut() {
+            let modified = modified_time(file);
+            if modified != *last_modified {
+                *last_modified = modified;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+#[inline]
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
 }
 
 #[inline(always)]
-fn get_descriptor(module: &wgpu::ShaderModule) -> wgpu::ProgrammableStageDescriptor {
+fn get_descriptor<'a>(
+    module: &'a wgpu::ShaderModule,
+    entry_point: &'a str,
+) -> wgpu::ProgrammableStageDescriptor<'a> {
     wgpu::ProgrammableStageDescriptor {
         module,
-        entry_point: "main",
+        entry_point,
     }
 }
 
-pub struct VertexShader {
-    module: wgpu::ShaderModule,
+const DEFAULT_ENTRY_POINT: &str = "main";
+
+// Every shader stage has the same constructors and only differs in the stage
+// it's compiled and reflected as
+macro_rules! shader_stage {
+    ($shader:ident, $stage:expr, $kind:ident) => {
+        pub struct $shader {
+            module: wgpu::ShaderModule,
+            reflection: ShaderReflection,
+            entry_point: String,
+            warnings: Vec<ShaderDiagnostic>,
+            // Only shaders compiled from a file can be recompiled
+            source: Option<ShaderSource>,
+        }
+
+        impl $shader {
+            /// Compiles the shader file, files with a .wgsl extension are compiled as WGSL
+            /// and everything else as GLSL.
+            #[cfg(any(feature = "shaderc", feature = "wgsl"))]
+            pub fn new(device: &wgpu::Device, path: impl AsRef<Path>) -> Result<Self, RenderError> {
+                Self::with_options(device, path, &ShaderCompileOptions::default())
+            }
+
+            #[cfg(any(feature = "shaderc", feature = "wgsl"))]
+            pub fn with_options(
+                device: &wgpu::Device,
+                path: impl AsRef<Path>,
+                options: &ShaderCompileOptions,
+            ) -> Result<Self, RenderError> {
+                let language = ShaderLanguage::from_path(path.as_ref());
+                Self::with_language(device, path, language, options)
+            }
+
+            /// Compiles the shader file as the given language regardless of its extension.
+            #[cfg(any(feature = "shaderc", feature = "wgsl"))]
+            pub fn with_language(
+                device: &wgpu::Device,
+                path: impl AsRef<Path>,
+                language: ShaderLanguage,
+                options: &ShaderCompileOptions,
+            ) -> Result<Self, RenderError> {
+                let path = path.as_ref();
+                let compiled = compile_file(path, language, $stage, options)?;
+                let source = ShaderSource::new(path, language, options, compiled.included_files);
+                Self::from_compiled(device, &compiled.spirv, compiled.warnings, Some(source))
+            }
+
+            /// Compiles GLSL source that isn't read from a file, e.g embedded with include_str!.
+            /// The name is used in error messages and quoted includes are resolved relative to it.
+            #[cfg(feature = "shaderc")]
+            pub fn from_glsl(
+                device: &wgpu::Device,
+                name: &str,
+                glsl: &str,
+                options: &ShaderCompileOptions,
+            ) -> Result<Self, RenderError> {
+                let compiled = compile_glsl_source(glsl, name, ShaderKind::$kind, options)?;
+                Self::from_compiled(device, &compiled.spirv, compiled.warnings, None)
+            }
+
+            /// Compiles WGSL source that isn't read from a file, the name is used in error
+            /// messages. Only the config of the options applies to WGSL.
+            #[cfg(feature = "wgsl")]
+            pub fn from_wgsl(
+                device: &wgpu::Device,
+                name: &str,
+                wgsl: &str,
+                options: &ShaderCompileOptions,
+            ) -> Result<Self, RenderError> {
+                let compiled = compile_wgsl(wgsl, name, &options.get_config())?;
+                Self::from_compiled(device, &compiled.spirv, compiled.warnings, None)
+            }
+
+            /// Creates the shader from precompiled SPIR-V, shaderc isn't needed for this.
+            pub fn from_spirv(device: &wgpu::Device, spirv: &[u32]) -> Result<Self, RenderError> {
+                Self::from_compiled(device, spirv, Vec::new(), None)
+            }
+
+            pub fn from_spirv_bytes(
+                device: &wgpu::Device,
+                bytes: &[u8],
+            ) -> Result<Self, RenderError> {
+                Self::from_compiled(device, &spirv_words(bytes)?, Vec::new(), None)
+            }
+
+            pub(crate) fn from_compiled(
+                device: &wgpu::Device,
+                spirv: &[u32],
+                warnings: Vec<ShaderDiagnostic>,
+                source: Option<ShaderSource>,
+            ) -> Result<Self, RenderError> {
+                let reflection = ShaderReflection::new(spirv)?;
+                let module = device.create_shader_module(ShaderModuleSource::SpirV(spirv));
+                Ok($shader {
+                    module,
+                    reflection,
+                    entry_point: DEFAULT_ENTRY_POINT.to_string(),
+                    warnings,
+                    source,
+                })
+            }
+
+            /// The warnings reported by the compiler, empty for shaders created from SPIR-V
+            pub fn get_warnings(&self) -> &[ShaderDiagnostic] {
+                &self.warnings
+            }
+
+            /// The path of the source file if the shader was compiled from one
+            pub fn get_path(&self) -> Option<&Path> {
+                self.source.as_ref().map(|source| source.path.as_path())
+            }
+
+            /// Returns true if the source file or any of its includes have been
+            /// modified since the last poll or since the shader was compiled.
+            /// Always false for shaders that weren't compiled from a file.
+            pub fn poll_changed(&mut self) -> bool {
+                self.source
+                    .as_mut()
+                    .map_or(false, ShaderSource::poll_changed)
+            }
+
+            /// Same as poll_changed but the change is still reported on the next call
+            pub fn has_changed(&self) -> bool {
+                self.source
+                    .as_ref()
+                    .map_or(false, ShaderSource::has_changed)
+            }
+
+            /// Compiles the source file again, the current shader is left untouched.
+            /// Returns None if the shader wasn't compiled from a file.
+            #[cfg(any(feature = "shaderc", feature = "wgsl"))]
+            pub fn recompile(&self, device: &wgpu::Device) -> Option<Result<Self, RenderError>> {
+                self.source.as_ref().map(|source| {
+                    Self::with_language(device, &source.path, source.language, &source.options)
+                        .map(|shader| shader.set_entry_point(&self.entry_point))
+                })
+            }
+
+            /// Selects which entry point of the module to use, defaults to main. The entry
+            /// point is verified to exist when the shader is used to build a node. Shaders
+            /// compiled from GLSL only have a main entry point.
+            pub fn set_entry_point(mut self, entry_point: &str) -> Self {
+                self.entry_point = entry_point.to_string();
+                self
+            }
+
+            pub fn get_entry_point(&self) -> &str {
+                &self.entry_point
+            }
+
+            pub(crate) fn get_descriptor(&self) -> wgpu::ProgrammableStageDescriptor {
+                get_descriptor(&self.module, &self.entry_point)
+            }
+
+            pub(crate) fn get_entry_point_reflection(
+                &self,
+            ) -> Result<&ReflectedEntryPoint, RenderError> {
+                self.reflection.get_entry_point(&self.entry_point, $stage)
+            }
+        }
+    };
 }
 
-impl VertexShader {
-    pub fn new(device: &wgpu::Device, path: impl AsRef<Path>) -> Result<VertexShader, RenderError> {
-        let data = compile_glsl(path, ShaderKind::Vertex)?;
-        let module = device.create_shader_module(ShaderModuleSource::SpirV(&data.as_binary()));
-        Ok(VertexShader { module })
+shader_stage!(VertexShader, wgpu::ShaderStage::VERTEX, Vertex);
+shader_stage!(FragmentShader, wgpu::ShaderStage::FRAGMENT, Fragment);
+shader_stage!(ComputeShader, wgpu::ShaderStage::COMPUTE, Compute);
+
+#[cfg(all(test, any(feature = "shaderc", feature = "wgsl")))]
+mod tests {
+
+    use super::*;
+    #[cfg(feature = "shaderc")]
+    use crate::test_utils::{test_dir, write_file};
+
+    #[cfg(feature = "shaderc")]
+    #[test]
+    fn includes() -> Result<(), RenderError> {
+        let dir = test_dir("includes");
+        let shader_dir = dir.join("shaders");
+        let include_dir = dir.join("include");
+        std::fs::create_dir_all(&shader_dir)?;
+        std::fs::create_dir_all(&include_dir)?;
+        let path = shader_dir.join("includes.fs");
+        let local_path = shader_dir.join("local.glsl");
+        let library_path = include_dir.join("library.glsl");
+        write_file(&local_path, "#include <library.glsl>\n");
+        write_file(&library_path, "const vec4 COLOR = vec4(1.0);\n");
+        let fragment = |include: &str| {
+            format!(
+                "#version 450\n#include {}\n\
+                 layout(location = 0) out vec4 f_color;\n\
+                 void main() {{ f_color = COLOR; }}\n",
+                include
+            )
+        };
+        let options = ShaderCompileOptions::new().add_include_dir(&include_dir);
+
+        // Quoted includes are resolved relative to the shader first and nested
+        // includes are tracked as well
+        write_file(&path, &fragment("\"local.glsl\""));
+        let compiled = compile_glsl(&path, ShaderKind::Fragment, &options)?;
+        assert_eq!(
+            compiled.included_files,
+            vec![local_path, library_path.clone()]
+        );
+        assert!(compile_glsl(&path, ShaderKind::Fragment, &ShaderCompileOptions::new()).is_err());
+
+        // Quoted includes fall back to the include dirs
+        write_file(&path, &fragment("\"library.glsl\""));
+        let compiled = compile_glsl(&path, ShaderKind::Fragment, &options)?;
+        assert_eq!(compiled.included_files, vec![library_path]);
+
+        // Angle bracket includes are only looked up in the include dirs
+        write_file(&path, &fragment("<local.glsl>"));
+        assert!(compile_glsl(&path, ShaderKind::Fragment, &options).is_err());
+        Ok(())
     }
 
-    pub(crate) fn get_descriptor(&self) -> wgpu::ProgrammableStageDescriptor {
-        get_descriptor(&self.module)
+    #[cfg(feature = "shaderc")]
+    #[test]
+    fn macro_definitions() -> Result<(), RenderError> {
+        let src = "#version 450\n\
+                   #if !defined(ENABLED) || VALUE != 2\n#error Missing defines\n#endif\n\
+                   layout(location = 0) out vec4 f_color;\n\
+                   void main() { f_color = vec4(1.0); }\n";
+        let options = ShaderCompileOptions::new().add_macro_definition("ENABLED", None);
+        assert!(compile_glsl_source(src, "defines.fs", ShaderKind::Fragment, &options).is_err());
+        let options = options.add_macro_definition("VALUE", Some("2"));
+        compile_glsl_source(src, "defines.fs", ShaderKind::Fragment, &options)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "shaderc")]
+    #[test]
+    fn include_changes() -> Result<(), RenderError> {
+        let dir = test_dir("include_changes");
+        let path = dir.join("changes.fs");
+        let include_path = dir.join("color.glsl");
+        write_file(&include_path, "const vec4 COLOR = vec4(1.0);\n");
+        write_file(
+            &path,
+            "#version 450\n#include \"color.glsl\"\n\
+             layout(location = 0) out vec4 f_color;\n\
+             void main() { f_color = COLOR; }\n",
+        );
+        let options = ShaderCompileOptions::new();
+        let compiled = compile_glsl(&path, ShaderKind::Fragment, &options)?;
+        let mut source = ShaderSource::new(
+            &path,
+            ShaderLanguage::Glsl,
+            &options,
+            compiled.included_files,
+        );
+        assert!(!source.has_changed());
+        write_file(&include_path, "const vec4 COLOR = vec4(0.5);\n");
+        assert!(source.has_changed());
+        assert!(source.poll_changed());
+        assert!(!source.poll_changed());
+        assert!(!source.has_changed());
+        Ok(())
     }
-}
 
-pub struct FragmentShader {
-    module: wgpu::ShaderModule,
+    #[cfg(feature = "shaderc")]
+    #[test]
+    fn glsl_entry_point() -> Result<(), RenderError> {
+        let src = "#version 450\n\
+                   void main() { gl_Position = vec4(0.0, 0.0, 0.0, 1.0); }\n";
+        let compiled = compile_glsl_source(
+            src,
+            "entry.vs",
+            ShaderKind::Vertex,
+            &ShaderCompileOptions::new(),
+        )?;
+        let reflection = ShaderReflection::new(&compiled.spirv)?;
+        reflection.get_entry_point(DEFAULT_ENTRY_POINT, wgpu::ShaderStage::VERTEX)?;
+        match reflection.get_entry_point("vs_main", wgpu::ShaderStage::VERTEX) {
+            Err(RenderError::MissingEntryPoint { name, .. }) => assert_eq!(name, "vs_main"),
+            _ => panic!("GLSL shaders should only have a main entry point"),
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "wgsl")]
+    #[test]
+    fn wgsl_entry_points() -> Result<(), RenderError> {
+        let src = r#"
+@vertex
+fn vs_main(@location(0) pos: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(pos, 1.0);
 }
 
-impl FragmentShader {
-    pub fn new(
-        device: &wgpu::Device,
-        path: impl AsRef<Path>,
-    ) -> Result<FragmentShader, RenderError> {
-        let data = compile_glsl(path.as_ref(), ShaderKind::Fragment)?;
-        let module = device.create_shader_module(ShaderModuleSource::SpirV(&data.as_binary()));
-        Ok(FragmentShader { module })
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0);
+}
+"#;
+        let reflection = ShaderReflection::new(
+            &compile_wgsl(src, "shader.wgsl", &ShaderCompileConfig::default())?.spirv,
+        )?;
+        let vertex = reflection.get_entry_point("vs_main", wgpu::ShaderStage::VERTEX)?;
+        assert_eq!(vertex.inputs.len(), 1);
+        reflection.get_entry_point("fs_main", wgpu::ShaderStage::FRAGMENT)?;
+        assert!(reflection
+            .get_entry_point("fs_main", wgpu::ShaderStage::VERTEX)
+            .is_err());
+        Ok(())
     }
 
-    pub(crate) fn get_descriptor(&self) -> wgpu::ProgrammableStageDescriptor {
-        get_descriptor(&self.module)
+    #[cfg(feature = "wgsl")]
+    #[test]
+    fn wgsl_error_location() {
+        let src =
+            "@fragment\nfn main() -> @location(0) vec4<f32> {\n    return vec4<f32>(foo);\n}\n";
+        match compile_wgsl(src, "broken.wgsl", &ShaderCompileConfig::default()) {
+            Err(RenderError::ShaderCompileError { diagnostics, .. }) => {
+                assert_eq!(diagnostics.len(), 1);
+                assert_eq!(diagnostics[0].line, Some(3));
+                assert_eq!(diagnostics[0].column, Some(22));
+            }
+            _ => panic!("The undefined identifier wasn't reported"),
+        }
     }
 }
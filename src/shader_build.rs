@@ -0,0 +1,127 @@
+// Helpers meant to be called from the build script of crates using smol-renderer
+// so the shaders can be compiled ahead of time and embedded with include_shader!
+use crate::{
+    shader::{compile_glsl, ShaderCompileOptions},
+    shader_diagnostics::ShaderDiagnostic,
+    RenderError,
+};
+use shaderc::ShaderKind;
+use std::path::{Path, PathBuf};
+
+/// Infers the shader kind from the .vs, .fs and .cs extensions
+pub fn shader_kind_from_path(path: impl AsRef<Path>) -> Option<ShaderKind> {
+    match path.as_ref().extension()?.to_str()? {
+        "vs" => Some(ShaderKind::Vertex),
+        "fs" => Some(ShaderKind::Fragment),
+        "cs" => Some(ShaderKind::Compute),
+        _ => None,
+    }
+}
+
+/// Compiles every shader in the directory and its subdirectories to `OUT_DIR/shaders`,
+/// keeping the relative path of the shader with a .spv extension appended. Cargo is
+/// told to rerun the build script if the directory, a shader or an include changes and
+/// the compiler warnings are shown as cargo warnings. Must be called from a build script
+/// since OUT_DIR is only set there.
+pub fn compile_shader_dir(
+    shader_dir: impl AsRef<Path>,
+    options: &ShaderCompileOptions,
+) -> Result<Vec<PathBuf>, RenderError> {
+    let out_dir = std::env::var_os("OUT_DIR")
+        .expect("OUT_DIR isn't set, compile_shader_dir must be called from a build script");
+    let compiled =
+        compile_shader_dir_to(&shader_dir, PathBuf::from(out_dir).join("shaders"), options)?;
+    println!("cargo:rerun-if-changed={}", shader_dir.as_ref().display());
+    for file in compiled.iter().flat_map(|shader| shader.sources.iter()) {
+        println!("cargo:rerun-if-changed={}", file.display());
+    }
+    for warning in compiled.iter().flat_map(|shader| shader.warnings.iter()) {
+        // Cargo only shows the first line of every warning
+        for line in warning.to_string().lines() {
+            println!("cargo:warning={}", line);
+        }
+    }
+    Ok(compiled.into_iter().map(|shader| shader.output).collect())
+}
+
+struct CompiledShader {
+    output: PathBuf,
+    // The shader source followed by its includes
+    sources: Vec<PathBuf>,
+    warnings: Vec<ShaderDiagnostic>,
+}
+
+fn compile_shader_dir_to(
+    shader_dir: impl AsRef<Path>,
+    out_dir: impl AsRef<Path>,
+    options: &ShaderCompileOptions,
+) -> Result<Vec<CompiledShader>, RenderError> {
+    let mut compiled = Vec::new();
+    let mut dirs = vec![shader_dir.as_ref().to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let kind = match shader_kind_from_path(&path) {
+                Some(kind) => kind,
+                None => continue,
+            };
+            let shader = compile_glsl(&path, kind, options)?;
+            let relative_path = path
+                .strip_prefix(shader_dir.as_ref())
+                .expect("Shaders are found within the shader directory");
+            let mut file_name = relative_path.as_os_str().to_os_string();
+            file_name.push(".spv");
+            let output = out_dir.as_ref().join(file_name);
+            if let Some(parent) = output.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let bytes = shader
+                .spirv
+                .iter()
+                .flat_map(|word| word.to_le_bytes().to_vec())
+                .collect::<Vec<u8>>();
+            std::fs::write(&output, bytes)?;
+            compiled.push(CompiledShader {
+                output,
+                sources: std::iter::once(path).chain(shader.included_files).collect(),
+                warnings: shader.warnings,
+            });
+        }
+    }
+    Ok(compiled)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::test_utils::test_dir;
+
+    #[test]
+    fn compile_example_shaders() -> Result<(), RenderError> {
+        let out_dir = test_dir("shader_build");
+        let compiled =
+            compile_shader_dir_to("examples/basic", &out_dir, &ShaderCompileOptions::default())?;
+        let mut outputs = compiled
+            .iter()
+            .map(|shader| shader.output.strip_prefix(&out_dir).unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        outputs.sort();
+        assert_eq!(
+            outputs,
+            vec![
+                PathBuf::from("shader.fs.spv"),
+                PathBuf::from("shader.vs.spv")
+            ]
+        );
+        for shader in compiled.iter() {
+            let spirv = std::fs::read(&shader.output)?;
+            assert_eq!(&spirv[..4], &0x0723_0203u32.to_le_bytes());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+// On-disk cache of compiled SPIR-V. Every entry is stored as <key>.spv together with
+// <key>.deps listing the included files and a hash of their contents at the time
+// of compilation and <key>.warnings with the warnings reported by the compiler.
+// The key covers everything else that affects the output.
+use crate::{
+    shader::{CompiledSpirV, ShaderCompileOptions},
+    shader_diagnostics::{format_diagnostics, parse_glslang_output},
+    RenderError,
+};
+use shaderc::ShaderKind;
+use std::{
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+};
+
+// Bump when the layout of the cache entries changes
+const CACHE_VERSION: u32 = 2;
+
+// FNV-1a, unlike DefaultHasher its output doesn't change between Rust versions
+// so the cache stays valid when the crate is rebuilt with a different toolchain
+struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+    fn default() -> Self {
+        FnvHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+pub(crate) fn cache_key(
+    src: &str,
+    name: &str,
+    shader_type: ShaderKind,
+    options: &ShaderCompileOptions,
+) -> u64 {
+    let mut hasher = FnvHasher::default();
+    CACHE_VERSION.hash(&mut hasher);
+    // shaderc doesn't report its own version, the SPIR-V version it generates and the
+    // version of this crate which pins the shaderc dependency are used instead
+    shaderc::get_spirv_version().hash(&mut hasher);
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    src.hash(&mut hasher);
+    // Relative includes are resolved from the name
+    name.hash(&mut hasher);
+    format!("{:?}", shader_type).hash(&mut hasher);
+    options.hash_inputs(&mut hasher);
+    hasher.finish()
+}
+
+fn hash_file(path: &Path) -> Option<u64> {
+    let content = std::fs::read(path).ok()?;
+    let mut hasher = FnvHasher::default();
+    content.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+fn entry_paths(cache_dir: &Path, key: u64) -> (PathBuf, PathBuf, PathBuf) {
+    (
+        cache_dir.join(format!("{:016x}.spv", key)),
+        cache_dir.join(format!("{:016x}.deps", key)),
+        cache_dir.join(format!("{:016x}.warnings", key)),
+    )
+}
+
+/// Returns the cached compilation if the entry exists and none
+/// of the included files have changed since it was stored.
+pub(crate) fn load(cache_dir: &Path, key: u64) -> Option<CompiledSpirV> {
+    let (spirv_path, deps_path, warnings_path) = entry_paths(cache_dir, key);
+    let deps = std::fs::read_to_string(deps_path).ok()?;
+    let mut included_files = Vec::new();
+    for line in deps.lines() {
+        let mut parts = line.splitn(2, ' ');
+        let hash = u64::from_str_radix(parts.next()?, 16).ok()?;
+        let path = PathBuf::from(parts.next()?);
+        if hash_file(&path)? != hash {
+            return None;
+        }
+        included_files.push(path);
+    }
+    let bytes = std::fs::read(spirv_path).ok()?;
+    if bytes.is_empty() || bytes.len() % 4 != 0 {
+        return None;
+    }
+    let spirv = bytes
+        .chunks_exact(4)
+        .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect();
+    let warnings = std::fs::read_to_string(warnings_path).ok()?;
+    Some(CompiledSpirV {
+        spirv,
+        included_files,
+        warnings: parse_glslang_output(&warnings, Path::new("")),
+    })
+}
+
+pub(crate) fn store(
+    cache_dir: &Path,
+    key: u64,
+    compiled: &CompiledSpirV,
+) -> Result<(), RenderError> {
+    std::fs::create_dir_all(cache_dir)?;
+    let (spirv_path, deps_path, warnings_path) = entry_paths(cache_dir, key);
+    let deps = compiled
+        .included_files
+        .iter()
+        .filter_map(|path| {
+            hash_file(path).map(|hash| format!("{:016x} {}\n", hash, path.display()))
+        })
+        .collect::<String>();
+    let bytes = compiled
+        .spirv
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .collect::<Vec<u8>>();
+    std::fs::write(spirv_path, bytes)?;
+    std::fs::write(warnings_path, format_diagnostics(&compiled.warnings))?;
+    // The deps file is written last so a partially written entry is never loaded
+    std::fs::write(deps_path, deps)?;
+    Ok(())
+}
+
+/// Removes every cached shader from the cache directory, other files are left as they are.
+pub fn clear_shader_cache(cache_dir: impl AsRef<Path>) -> Result<(), RenderError> {
+    let cache_dir = cache_dir.as_ref();
+    if !cache_dir.exists() {
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(cache_dir)? {
+        let path = entry?.path();
+        let is_cache_entry = path.extension().map_or(false, |extension| {
+            extension == "spv" || extension == "deps" || extension == "warnings"
+        });
+        if path.is_file() && is_cache_entry {
+            std::fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{
+        shader::compile_glsl,
+        test_utils::{test_dir, write_file},
+    };
+
+    #[test]
+    fn cached_compilation() -> Result<(), RenderError> {
+        let dir = test_dir("cached_compilation");
+        let cache_dir = dir.join("cache");
+        let path = dir.join("cached.fs");
+        let include_path = dir.join("color.glsl");
+        write_file(&include_path, "const vec4 COLOR = vec4(1.0);\n");
+        // The unknown extension makes glslang report a warning
+        let src = "#version 450\n\
+                   #extension GL_SMOL_unknown_extension : enable\n\
+                   #include \"color.glsl\"\n\
+                   layout(location = 0) out vec4 f_color;\n\
+                   void main() { f_color = COLOR; }\n";
+        write_file(&path, src);
+        let options = ShaderCompileOptions::new().set_cache_dir(&cache_dir);
+        let compiled = compile_glsl(&path, ShaderKind::Fragment, &options)?;
+        assert!(!compiled.warnings.is_empty());
+
+        // Appending an OpNop to the stored SPIR-V shows whether the entry is used
+        let key = cache_key(src, &path.to_string_lossy(), ShaderKind::Fragment, &options);
+        let (spirv_path, _, _) = entry_paths(&cache_dir, key);
+        let mut marked = compiled.spirv.clone();
+        marked.push(1 << 16);
+        let bytes = marked
+            .iter()
+            .flat_map(|word| word.to_le_bytes().to_vec())
+            .collect::<Vec<u8>>();
+        std::fs::write(&spirv_path, bytes)?;
+        let cached = compile_glsl(&path, ShaderKind::Fragment, &options)?;
+        assert_eq!(cached.spirv, marked);
+        assert_eq!(cached.included_files, compiled.included_files);
+        assert_eq!(cached.warnings, compiled.warnings);
+
+        // Editing the include invalidates the entry
+        write_file(&include_path, "const vec4 COLOR = vec4(0.5);\n");
+        let recompiled = compile_glsl(&path, ShaderKind::Fragment, &options)?;
+        assert_ne!(recompiled.spirv, marked);
+        assert_ne!(recompiled.spirv, compiled.spirv);
+        Ok(())
+    }
+}
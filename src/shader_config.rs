@@ -0,0 +1,140 @@
+use once_cell::sync::Lazy;
+use std::sync::RwLock;
+
+// Used by every shader whose ShaderCompileOptions don't set a config
+static GLOBAL_CONFIG: Lazy<RwLock<ShaderCompileConfig>> =
+    Lazy::new(|| RwLock::new(ShaderCompileConfig::default()));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShaderOptimizationLevel {
+    None,
+    Size,
+    Performance,
+}
+
+/// The environment the SPIR-V is generated for. wgpu only accepts Vulkan flavoured SPIR-V.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShaderTargetEnv {
+    Vulkan1_0,
+    Vulkan1_1,
+}
+
+/// Controls how shaders are compiled to SPIR-V. The default config matches the defaults
+/// of shaderc: no optimization, no debug info, Vulkan 1.0 and warnings are kept as
+/// warnings. WGSL shaders only use the debug info setting since naga doesn't optimize.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShaderCompileConfig {
+    optimization_level: ShaderOptimizationLevel,
+    debug_info: bool,
+    target_env: ShaderTargetEnv,
+    warnings_as_errors: bool,
+}
+
+impl Default for ShaderCompileConfig {
+    fn default() -> Self {
+        ShaderCompileConfig {
+            optimization_level: ShaderOptimizationLevel::None,
+            debug_info: false,
+            target_env: ShaderTargetEnv::Vulkan1_0,
+            warnings_as_errors: false,
+        }
+    }
+}
+
+impl ShaderCompileConfig {
+    pub fn new() -> Self {
+        ShaderCompileConfig::default()
+    }
+
+    /// Unoptimized SPIR-V with debug info so the shaders can be inspected in graphics debuggers
+    pub fn debug() -> Self {
+        ShaderCompileConfig::default().set_debug_info(true)
+    }
+
+    /// SPIR-V optimized for performance without debug info
+    pub fn release() -> Self {
+        ShaderCompileConfig::default().set_optimization_level(ShaderOptimizationLevel::Performance)
+    }
+
+    /// The debug config when debug assertions are enabled and the release config otherwise
+    pub fn from_build_profile() -> Self {
+        if cfg!(debug_assertions) {
+            ShaderCompileConfig::debug()
+        } else {
+            ShaderCompileConfig::release()
+        }
+    }
+
+    pub fn set_optimization_level(mut self, optimization_level: ShaderOptimizationLevel) -> Self {
+        self.optimization_level = optimization_level;
+        self
+    }
+
+    pub fn set_debug_info(mut self, debug_info: bool) -> Self {
+        self.debug_info = debug_info;
+        self
+    }
+
+    pub fn set_target_env(mut self, target_env: ShaderTargetEnv) -> Self {
+        self.target_env = target_env;
+        self
+    }
+
+    /// Compilation fails if the compiler reports any warnings
+    pub fn set_warnings_as_errors(mut self, warnings_as_errors: bool) -> Self {
+        self.warnings_as_errors = warnings_as_errors;
+        self
+    }
+
+    pub fn get_optimization_level(&self) -> ShaderOptimizationLevel {
+        self.optimization_level
+    }
+
+    pub fn get_debug_info(&self) -> bool {
+        self.debug_info
+    }
+
+    pub fn get_target_env(&self) -> ShaderTargetEnv {
+        self.target_env
+    }
+
+    pub fn get_warnings_as_errors(&self) -> bool {
+        self.warnings_as_errors
+    }
+
+    /// Uses the config for every shader compiled from now on unless
+    /// a config is set in the ShaderCompileOptions of the shader.
+    /// Shaders that are already compiled aren't affected until they're recompiled.
+    pub fn set_global(self) {
+        *GLOBAL_CONFIG
+            .write()
+            .expect("The global shader config lock is poisoned") = self;
+    }
+
+    pub fn global() -> Self {
+        *GLOBAL_CONFIG
+            .read()
+            .expect("The global shader config lock is poisoned")
+    }
+
+    #[cfg(feature = "shaderc")]
+    pub(crate) fn apply(&self, options: &mut shaderc::CompileOptions) {
+        use shaderc::{EnvVersion, OptimizationLevel, TargetEnv};
+        options.set_optimization_level(match self.optimization_level {
+            ShaderOptimizationLevel::None => OptimizationLevel::Zero,
+            ShaderOptimizationLevel::Size => OptimizationLevel::Size,
+            ShaderOptimizationLevel::Performance => OptimizationLevel::Performance,
+        });
+        if self.debug_info {
+            options.set_generate_debug_info();
+        }
+        let (target_env, version) = match self.target_env {
+            ShaderTargetEnv::Vulkan1_0 => (TargetEnv::Vulkan, EnvVersion::Vulkan1_0),
+            ShaderTargetEnv::Vulkan1_1 => (TargetEnv::Vulkan, EnvVersion::Vulkan1_1),
+        };
+        options.set_target_env(target_env, version as u32);
+        if self.warnings_as_errors {
+            options.set_warnings_as_errors();
+        }
+    }
+}
@@ -0,0 +1,205 @@
+#[cfg(feature = "shaderc")]
+use std::path::Path;
+use std::{fmt, path::PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for DiagnosticSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiagnosticSeverity::Error => write!(f, "error"),
+            DiagnosticSeverity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A single error or warning reported by the shader compiler. The line and column
+/// are 1-based, glslang only reports lines so the column is always None for GLSL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderDiagnostic {
+    pub file: PathBuf,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+}
+
+impl fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}: {}",
+            self.location(),
+            self.severity,
+            self.message
+        )
+    }
+}
+
+impl ShaderDiagnostic {
+    fn location(&self) -> String {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => format!("{}:{}:{}", self.file.display(), line, column),
+            (Some(line), None) => format!("{}:{}", self.file.display(), line),
+            _ => self.file.display().to_string(),
+        }
+    }
+
+    /// Formats the diagnostic followed by the offending source line with a caret under
+    /// the column, the whole line is underlined if the column isn't known. The source
+    /// is read from the file when it isn't given. Only the message is shown if the
+    /// line can't be found.
+    pub fn pretty_print(&self, source: Option<&str>) -> String {
+        let mut output = format!("{}: {}", self.severity, self.message);
+        let location = self.location();
+        let line_number = match self.line {
+            Some(line_number) => line_number,
+            None => {
+                output.push_str(&format!("\n --> {}", location));
+                return output;
+            }
+        };
+        let gutter = " ".repeat(line_number.to_string().len());
+        output.push_str(&format!("\n{}--> {}", gutter, location));
+        let file_source = match source {
+            Some(_) => None,
+            None => std::fs::read_to_string(&self.file).ok(),
+        };
+        let line = match source
+            .or_else(|| file_source.as_deref())
+            .and_then(|source| source.lines().nth(line_number.saturating_sub(1) as usize))
+        {
+            Some(line) => line.trim_end(),
+            None => return output,
+        };
+        let (start, length) = match self.column {
+            Some(column) => (column.saturating_sub(1) as usize, 1),
+            None => {
+                let code = line.trim_start();
+                (
+                    line.chars().count() - code.chars().count(),
+                    code.chars().count(),
+                )
+            }
+        };
+        // Tabs are kept so the caret lines up with the source line
+        let padding = line
+            .chars()
+            .take(start)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        output.push_str(&format!(
+            "\n{0} |\n{1} | {2}\n{0} | {3}{4}",
+            gutter,
+            line_number,
+            line,
+            padding,
+            "^".repeat(length.max(1))
+        ));
+        output
+    }
+}
+
+pub(crate) fn format_diagnostics(diagnostics: &[ShaderDiagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parses the messages of glslang which are formatted as "file:line: severity: message".
+/// Lines that don't follow the format are attached to the previous diagnostic
+/// and the "N errors generated." summary is skipped.
+#[cfg(feature = "shaderc")]
+pub(crate) fn parse_glslang_output(output: &str, file: &Path) -> Vec<ShaderDiagnostic> {
+    let mut diagnostics: Vec<ShaderDiagnostic> = Vec::new();
+    for line in output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        if line.ends_with(" generated.") {
+            continue;
+        } else if let Some(diagnostic) = parse_glslang_line(line) {
+            diagnostics.push(diagnostic);
+        } else if let Some(previous) = diagnostics.last_mut() {
+            previous.message.push('\n');
+            previous.message.push_str(line);
+        } else {
+            diagnostics.push(ShaderDiagnostic {
+                file: file.to_path_buf(),
+                line: None,
+                column: None,
+                severity: DiagnosticSeverity::Error,
+                message: line.to_string(),
+            });
+        }
+    }
+    diagnostics
+}
+
+#[cfg(feature = "shaderc")]
+fn parse_glslang_line(line: &str) -> Option<ShaderDiagnostic> {
+    let (index, marker, severity) = [
+        (": error: ", DiagnosticSeverity::Error),
+        (": warning: ", DiagnosticSeverity::Warning),
+    ]
+    .iter()
+    .filter_map(|(marker, severity)| line.find(marker).map(|index| (index, marker, *severity)))
+    .min_by_key(|(index, _, _)| *index)?;
+    let location = &line[..index];
+    let message = line[index + marker.len()..].to_string();
+    let mut parts = location.rsplitn(2, ':');
+    let (file, line) = match (parts.next(), parts.next()) {
+        (Some(line), Some(file)) if line.parse::<u32>().is_ok() => (file, line.parse().ok()),
+        _ => (location, None),
+    };
+    Some(ShaderDiagnostic {
+        file: PathBuf::from(file),
+        line,
+        column: None,
+        severity,
+        message,
+    })
+}
+
+#[cfg(all(test, feature = "shaderc"))]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parse_and_pretty_print() {
+        let output = "shaders/light.fs:3: error: 'colour' : undeclared identifier\n\
+                      shaders/light.fs:3: error: '' : compilation terminated\n\
+                      2 errors generated.\n";
+        let diagnostics = parse_glslang_output(output, Path::new("shaders/light.fs"));
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].file, PathBuf::from("shaders/light.fs"));
+        assert_eq!(diagnostics[0].line, Some(3));
+        assert_eq!(diagnostics[0].severity, DiagnosticSeverity::Error);
+        assert_eq!(diagnostics[0].message, "'colour' : undeclared identifier");
+        assert_eq!(diagnostics[1].message, "'' : compilation terminated");
+
+        let source = "#version 450\nvoid main() {\n    f_color = colour;\n}\n";
+        assert_eq!(
+            diagnostics[0].pretty_print(Some(source)),
+            "error: 'colour' : undeclared identifier\n \
+             --> shaders/light.fs:3\n  |\n3 |     f_color = colour;\n  |     ^^^^^^^^^^^^^^^^^"
+        );
+        let diagnostic = ShaderDiagnostic {
+            column: Some(15),
+            ..diagnostics[0].clone()
+        };
+        assert_eq!(
+            diagnostic.pretty_print(Some(source)),
+            "error: 'colour' : undeclared identifier\n \
+             --> shaders/light.fs:3:15\n  |\n3 |     f_color = colour;\n  |               ^"
+        );
+    }
+}
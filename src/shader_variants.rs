@@ -0,0 +1,232 @@
+use crate::{
+    shader::{compile_glsl, CompiledSpirV, ShaderCompileOptions, ShaderLanguage, ShaderSource},
+    ComputeShader, FragmentShader, RenderError, VertexShader,
+};
+use shaderc::ShaderKind;
+use std::{
+    collections::{BTreeMap, HashMap},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for crate::VertexShader {}
+    impl Sealed for crate::FragmentShader {}
+    impl Sealed for crate::ComputeShader {}
+}
+
+/// The shader types that can be compiled as variants
+pub trait ShaderVariantKind: private::Sealed {
+    const KIND: ShaderKind;
+}
+
+impl ShaderVariantKind for VertexShader {
+    const KIND: ShaderKind = ShaderKind::Vertex;
+}
+
+impl ShaderVariantKind for FragmentShader {
+    const KIND: ShaderKind = ShaderKind::Fragment;
+}
+
+impl ShaderVariantKind for ComputeShader {
+    const KIND: ShaderKind = ShaderKind::Compute;
+}
+
+/// The set of defines that identifies a variant. Flags are defined without a value
+/// which is enough for #ifdef toggles.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ShaderDefines {
+    defines: BTreeMap<String, Option<String>>,
+}
+
+impl ShaderDefines {
+    pub fn new() -> Self {
+        ShaderDefines::default()
+    }
+
+    pub fn flag(mut self, name: &str) -> Self {
+        self.defines.insert(name.to_string(), None);
+        self
+    }
+
+    pub fn value(mut self, name: &str, value: &str) -> Self {
+        self.defines
+            .insert(name.to_string(), Some(value.to_string()));
+        self
+    }
+
+    fn apply(&self, options: &ShaderCompileOptions) -> ShaderCompileOptions {
+        self.defines
+            .iter()
+            .fold(options.clone(), |options, (name, value)| {
+                options.add_macro_definition(name, value.as_deref())
+            })
+    }
+}
+
+// A compiled variant together with the modification times of the files it was compiled from
+struct CachedVariant {
+    compiled: Arc<CompiledSpirV>,
+    source: ShaderSource,
+}
+
+/// A single GLSL source compiled with different sets of defines. Every variant is
+/// compiled the first time it's requested and the SPIR-V is cached for later requests.
+/// A cached variant is compiled again when the source or any of its includes change.
+pub struct ShaderVariants<S: ShaderVariantKind> {
+    path: PathBuf,
+    options: ShaderCompileOptions,
+    variants: Mutex<HashMap<ShaderDefines, CachedVariant>>,
+    _marker: PhantomData<fn() -> S>,
+}
+
+impl<S: ShaderVariantKind> ShaderVariants<S> {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self::with_options(path, ShaderCompileOptions::default())
+    }
+
+    /// The options are shared by every variant, the defines of
+    /// the variants are added on top of them.
+    pub fn with_options(path: impl AsRef<Path>, options: ShaderCompileOptions) -> Self {
+        ShaderVariants {
+            path: path.as_ref().to_path_buf(),
+            options,
+            variants: Mutex::new(HashMap::new()),
+            _marker: PhantomData::default(),
+        }
+    }
+
+    /// Number of variants in the cache
+    pub fn compiled_count(&self) -> usize {
+        self.lock_variants().len()
+    }
+
+    fn lock_variants(&self) -> MutexGuard<HashMap<ShaderDefines, CachedVariant>> {
+        self.variants
+            .lock()
+            .expect("The shader variant cache lock is poisoned")
+    }
+
+    // Returns the cached variant unless its files changed since it was compiled. The source
+    // keeps the modification times from when it was compiled so reload_shaders notices any
+    // change made after that.
+    fn compile_variant(
+        &self,
+        defines: &ShaderDefines,
+    ) -> Result<(Arc<CompiledSpirV>, ShaderSource), RenderError> {
+        if let Some(cached) = self.lock_variants().get(defines) {
+            if !cached.source.has_changed() {
+                return Ok((cached.compiled.clone(), cached.source.clone()));
+            }
+        }
+        let options = defines.apply(&self.options);
+        let compiled = Arc::new(compile_glsl(&self.path, S::KIND, &options)?);
+        let source = ShaderSource::new(
+            &self.path,
+            ShaderLanguage::Glsl,
+            &options,
+            compiled.included_files.clone(),
+        );
+        self.lock_variants().insert(
+            defines.clone(),
+            CachedVariant {
+                compiled: compiled.clone(),
+                source: source.clone(),
+            },
+        );
+        Ok((compiled, source))
+    }
+}
+
+impl ShaderVariants<VertexShader> {
+    pub fn get(
+        &self,
+        device: &wgpu::Device,
+        defines: &ShaderDefines,
+    ) -> Result<VertexShader, RenderError> {
+        let (compiled, source) = self.compile_variant(defines)?;
+        VertexShader::from_compiled(
+            device,
+            &compiled.spirv,
+            compiled.warnings.clone(),
+            Some(source),
+        )
+    }
+}
+
+impl ShaderVariants<FragmentShader> {
+    pub fn get(
+        &self,
+        device: &wgpu::Device,
+        defines: &ShaderDefines,
+    ) -> Result<FragmentShader, RenderError> {
+        let (compiled, source) = self.compile_variant(defines)?;
+        FragmentShader::from_compiled(
+            device,
+            &compiled.spirv,
+            compiled.warnings.clone(),
+            Some(source),
+        )
+    }
+}
+
+impl ShaderVariants<ComputeShader> {
+    pub fn get(
+        &self,
+        device: &wgpu::Device,
+        defines: &ShaderDefines,
+    ) -> Result<ComputeShader, RenderError> {
+        let (compiled, source) = self.compile_variant(defines)?;
+        ComputeShader::from_compiled(
+            device,
+            &compiled.spirv,
+            compiled.warnings.clone(),
+            Some(source),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::test_utils::{test_dir, write_file};
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn variants_follow_source_changes() -> Result<(), RenderError> {
+        assert_send_sync::<ShaderVariants<VertexShader>>();
+        let dir = test_dir("variants_follow_source_changes");
+        let path = dir.join("variant.fs");
+        let include_path = dir.join("color.glsl");
+        write_file(&include_path, "const vec4 COLOR = vec4(1.0);\n");
+        write_file(
+            &path,
+            "#version 450\n#include \"color.glsl\"\n\
+             layout(location = 0) out vec4 f_color;\n\
+             void main() {\n\
+             #ifdef HALF\n    f_color = COLOR * 0.5;\n#else\n    f_color = COLOR;\n#endif\n}\n",
+        );
+        let variants = ShaderVariants::<FragmentShader>::new(&path);
+        let half = ShaderDefines::new().flag("HALF");
+        let (first, _) = variants.compile_variant(&half)?;
+        let (cached, source) = variants.compile_variant(&half)?;
+        assert!(Arc::ptr_eq(&first, &cached));
+        assert!(!source.has_changed());
+        variants.compile_variant(&ShaderDefines::new())?;
+        assert_eq!(variants.compiled_count(), 2);
+
+        // Editing an include invalidates the cached variant
+        write_file(&include_path, "const vec4 COLOR = vec4(0.25);\n");
+        assert!(source.has_changed());
+        let (recompiled, source) = variants.compile_variant(&half)?;
+        assert!(!Arc::ptr_eq(&first, &recompiled));
+        assert_ne!(first.spirv, recompiled.spirv);
+        assert!(!source.has_changed());
+        assert_eq!(variants.compiled_count(), 2);
+        Ok(())
+    }
+}
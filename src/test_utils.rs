@@ -0,0 +1,58 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+pub(crate) fn create_test_env() -> (wgpu::Device, wgpu::Queue) {
+    futures::executor::block_on(async {
+        let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::Default,
+                compatible_surface: None,
+            })
+            .await
+            .unwrap();
+
+        let adapter_features = adapter.features();
+        adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    features: adapter_features,
+                    shader_validation: false,
+                    limits: Default::default(),
+                },
+                None,
+            )
+            .await
+            .unwrap()
+    })
+}
+
+// An empty directory in the system temp dir, every test should use its own name
+pub(crate) fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("smol_renderer_{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+// Writes the file and waits until the modification time differs from the previous one
+// since some file systems only store it with a resolution of a second or more
+pub(crate) fn write_file(path: &Path, contents: &str) {
+    let previous = modified_time(path);
+    loop {
+        std::fs::write(path, contents).unwrap();
+        if previous.is_none() || modified_time(path) != previous {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
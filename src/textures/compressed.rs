@@ -0,0 +1,599 @@
+use super::{
+    mipmaps::full_mip_level_count, pixel_format::ColorSpace, LoadableTexture, TextureData,
+    TextureLoadOptions, TextureShaderLayout,
+};
+use crate::RenderError;
+use once_cell::sync::OnceCell;
+use std::{convert::TryFrom, marker::PhantomData, path::Path};
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+
+// DDS header flags
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+const DDPF_FOURCC: u32 = 0x4;
+const DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+
+fn invalid(reason: impl Into<String>) -> RenderError {
+    RenderError::CompressedTextureError {
+        reason: reason.into(),
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, RenderError> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|value| u32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+        .ok_or_else(|| invalid("Unexpected end of file"))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, RenderError> {
+    let low = read_u32(bytes, offset)?;
+    let high = read_u32(bytes, offset + 4)?;
+    Ok(u64::from(high) << 32 | u64::from(low))
+}
+
+fn read_range(bytes: &[u8], offset: usize, length: usize) -> Result<&[u8], RenderError> {
+    offset
+        .checked_add(length)
+        .and_then(|end| bytes.get(offset..end))
+        .ok_or_else(|| invalid("Unexpected end of file"))
+}
+
+// The width and height of the blocks of the format and their size in bytes,
+// uncompressed formats have 1x1 blocks
+fn block_info(format: wgpu::TextureFormat) -> (u32, u32) {
+    match format {
+        wgpu::TextureFormat::Bc1RgbaUnorm
+        | wgpu::TextureFormat::Bc1RgbaUnormSrgb
+        | wgpu::TextureFormat::Bc4RUnorm
+        | wgpu::TextureFormat::Bc4RSnorm => (4, 8),
+        wgpu::TextureFormat::Bc2RgbaUnorm
+        | wgpu::TextureFormat::Bc2RgbaUnormSrgb
+        | wgpu::TextureFormat::Bc3RgbaUnorm
+        | wgpu::TextureFormat::Bc3RgbaUnormSrgb
+        | wgpu::TextureFormat::Bc5RgUnorm
+        | wgpu::TextureFormat::Bc5RgSnorm
+        | wgpu::TextureFormat::Bc6hRgbUfloat
+        | wgpu::TextureFormat::Bc6hRgbSfloat
+        | wgpu::TextureFormat::Bc7RgbaUnorm
+        | wgpu::TextureFormat::Bc7RgbaUnormSrgb => (4, 16),
+        wgpu::TextureFormat::Rgba16Float => (1, 8),
+        _ => (1, 4),
+    }
+}
+
+fn is_bc_format(format: wgpu::TextureFormat) -> bool {
+    block_info(format).0 == 4
+}
+
+// Switches between the sRGB and linear variant of the format if there is one
+fn with_color_space(format: wgpu::TextureFormat, color_space: ColorSpace) -> wgpu::TextureFormat {
+    use wgpu::TextureFormat::*;
+    match (format, color_space) {
+        (Rgba8Unorm, ColorSpace::Srgb) => Rgba8UnormSrgb,
+        (Rgba8UnormSrgb, ColorSpace::Linear) => Rgba8Unorm,
+        (Bc1RgbaUnorm, ColorSpace::Srgb) => Bc1RgbaUnormSrgb,
+        (Bc1RgbaUnormSrgb, ColorSpace::Linear) => Bc1RgbaUnorm,
+        (Bc2RgbaUnorm, ColorSpace::Srgb) => Bc2RgbaUnormSrgb,
+        (Bc2RgbaUnormSrgb, ColorSpace::Linear) => Bc2RgbaUnorm,
+        (Bc3RgbaUnorm, ColorSpace::Srgb) => Bc3RgbaUnormSrgb,
+        (Bc3RgbaUnormSrgb, ColorSpace::Linear) => Bc3RgbaUnorm,
+        (Bc7RgbaUnorm, ColorSpace::Srgb) => Bc7RgbaUnormSrgb,
+        (Bc7RgbaUnormSrgb, ColorSpace::Linear) => Bc7RgbaUnorm,
+        (format, _) => format,
+    }
+}
+
+// Size of a mip level rounded up to whole blocks
+fn physical_size(format: wgpu::TextureFormat, width: u32, height: u32, level: u32) -> (u32, u32) {
+    let (block_size, _) = block_info(format);
+    let round_up = |size: u32| {
+        let size = (size >> level).max(1);
+        size.div_ceil(block_size) * block_size
+    };
+    (round_up(width), round_up(height))
+}
+
+// The byte length of a mip level of every layer, the size must have been validated
+fn level_byte_length(
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    level: u32,
+    array_layer_count: u32,
+) -> Result<usize, RenderError> {
+    let (block_size, block_bytes) = block_info(format);
+    let (width, height) = physical_size(format, width, height, level);
+    [height / block_size, block_bytes, array_layer_count]
+        .iter()
+        .try_fold((width / block_size) as usize, |length, &factor| {
+            length.checked_mul(factor as usize)
+        })
+        .ok_or_else(|| invalid("The texture is too large"))
+}
+
+// Rejects sizes wgpu can't create a texture with and more levels than the size allows
+fn validate_size(
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    level_count: u32,
+) -> Result<(), RenderError> {
+    let (block_size, _) = block_info(format);
+    if width == 0 || height == 0 || width % block_size != 0 || height % block_size != 0 {
+        return Err(invalid(format!(
+            "The size {}x{} isn't a non zero multiple of the {}x{} blocks of {:?}",
+            width, height, block_size, block_size, format
+        )));
+    }
+    let max_level_count = full_mip_level_count(width, height);
+    if level_count > max_level_count {
+        return Err(invalid(format!(
+            "The file has {} mip levels but a {}x{} texture can only have {}",
+            level_count, width, height, max_level_count
+        )));
+    }
+    Ok(())
+}
+
+fn to_usize(value: u64) -> Result<usize, RenderError> {
+    usize::try_from(value).map_err(|_| invalid("Offset out of range"))
+}
+
+fn ktx2_format(vk_format: u32) -> Result<wgpu::TextureFormat, RenderError> {
+    use wgpu::TextureFormat::*;
+    let format = match vk_format {
+        37 => Rgba8Unorm,
+        43 => Rgba8UnormSrgb,
+        97 => Rgba16Float,
+        // BC1 without alpha is stored the same way as with alpha
+        131 | 133 => Bc1RgbaUnorm,
+        132 | 134 => Bc1RgbaUnormSrgb,
+        135 => Bc2RgbaUnorm,
+        136 => Bc2RgbaUnormSrgb,
+        137 => Bc3RgbaUnorm,
+        138 => Bc3RgbaUnormSrgb,
+        139 => Bc4RUnorm,
+        140 => Bc4RSnorm,
+        141 => Bc5RgUnorm,
+        142 => Bc5RgSnorm,
+        143 => Bc6hRgbUfloat,
+        144 => Bc6hRgbSfloat,
+        145 => Bc7RgbaUnorm,
+        146 => Bc7RgbaUnormSrgb,
+        147..=156 => {
+            return Err(invalid(
+                "ETC2 and EAC textures can't be represented by this wgpu version",
+            ))
+        }
+        157..=184 => {
+            return Err(invalid(
+                "ASTC textures can't be represented by this wgpu version",
+            ))
+        }
+        _ => return Err(invalid(format!("Unsupported VkFormat {}", vk_format))),
+    };
+    Ok(format)
+}
+
+fn dxgi_format(dxgi_format: u32) -> Result<wgpu::TextureFormat, RenderError> {
+    use wgpu::TextureFormat::*;
+    let format = match dxgi_format {
+        10 => Rgba16Float,
+        28 => Rgba8Unorm,
+        29 => Rgba8UnormSrgb,
+        71 => Bc1RgbaUnorm,
+        72 => Bc1RgbaUnormSrgb,
+        74 => Bc2RgbaUnorm,
+        75 => Bc2RgbaUnormSrgb,
+        77 => Bc3RgbaUnorm,
+        78 => Bc3RgbaUnormSrgb,
+        80 => Bc4RUnorm,
+        81 => Bc4RSnorm,
+        83 => Bc5RgUnorm,
+        84 => Bc5RgSnorm,
+        95 => Bc6hRgbUfloat,
+        96 => Bc6hRgbSfloat,
+        98 => Bc7RgbaUnorm,
+        99 => Bc7RgbaUnormSrgb,
+        _ => return Err(invalid(format!("Unsupported DXGI format {}", dxgi_format))),
+    };
+    Ok(format)
+}
+
+fn four_cc_format(four_cc: &[u8]) -> Result<wgpu::TextureFormat, RenderError> {
+    use wgpu::TextureFormat::*;
+    let format = match four_cc {
+        b"DXT1" => Bc1RgbaUnorm,
+        b"DXT2" | b"DXT3" => Bc2RgbaUnorm,
+        b"DXT4" | b"DXT5" => Bc3RgbaUnorm,
+        b"ATI1" | b"BC4U" => Bc4RUnorm,
+        b"BC4S" => Bc4RSnorm,
+        b"ATI2" | b"BC5U" => Bc5RgUnorm,
+        b"BC5S" => Bc5RgSnorm,
+        _ => {
+            return Err(invalid(format!(
+                "Unsupported DDS FourCC {}",
+                String::from_utf8_lossy(four_cc)
+            )))
+        }
+    };
+    Ok(format)
+}
+
+// The mip levels and array layers of a KTX2 or DDS file
+#[derive(Debug)]
+pub(crate) struct CompressedImage {
+    pub(crate) format: wgpu::TextureFormat,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    // Cube maps are stored as six layers per cube
+    pub(crate) array_layer_count: u32,
+    // Every level contains the images of all layers one after another
+    pub(crate) levels: Vec<Vec<u8>>,
+}
+
+impl CompressedImage {
+    /// Opens .ktx2 and .dds files, the container is detected from the file contents
+    pub(crate) fn open(path: impl AsRef<Path>) -> Result<CompressedImage, RenderError> {
+        let bytes = std::fs::read(path)?;
+        if bytes.starts_with(&KTX2_IDENTIFIER) {
+            CompressedImage::from_ktx2(&bytes)
+        } else if bytes.starts_with(DDS_MAGIC) {
+            CompressedImage::from_dds(&bytes)
+        } else {
+            Err(invalid("The file is neither a KTX2 nor a DDS file"))
+        }
+    }
+
+    pub(crate) fn from_ktx2(bytes: &[u8]) -> Result<CompressedImage, RenderError> {
+        let format = ktx2_format(read_u32(bytes, 12)?)?;
+        let width = read_u32(bytes, 20)?;
+        // 1D textures have a height of 0
+        let height = read_u32(bytes, 24)?.max(1);
+        let depth = read_u32(bytes, 28)?;
+        let layer_count = read_u32(bytes, 32)?.max(1);
+        let face_count = read_u32(bytes, 36)?.max(1);
+        let level_count = read_u32(bytes, 40)?.max(1);
+        let supercompression = read_u32(bytes, 44)?;
+        if depth > 1 {
+            return Err(invalid("3D textures aren't supported"));
+        }
+        if supercompression != 0 {
+            return Err(invalid("Supercompressed KTX2 files aren't supported"));
+        }
+        validate_size(format, width, height, level_count)?;
+        let array_layer_count = layer_count
+            .checked_mul(face_count)
+            .ok_or_else(|| invalid("Too many array layers"))?;
+        let mut levels = Vec::with_capacity(level_count as usize);
+        for level in 0..level_count {
+            // The level index follows the 80 byte header
+            let index = 80 + level as usize * 24;
+            let offset = to_usize(read_u64(bytes, index)?)?;
+            let length = to_usize(read_u64(bytes, index + 8)?)?;
+            let expected = level_byte_length(format, width, height, level, array_layer_count)?;
+            if length != expected {
+                return Err(invalid(format!(
+                    "Mip level {} is {} bytes but {} are expected",
+                    level, length, expected
+                )));
+            }
+            levels.push(read_range(bytes, offset, length)?.to_vec());
+        }
+        Ok(CompressedImage {
+            format,
+            width,
+            height,
+            array_layer_count,
+            levels,
+        })
+    }
+
+    pub(crate) fn from_dds(bytes: &[u8]) -> Result<CompressedImage, RenderError> {
+        let flags = read_u32(bytes, 8)?;
+        let height = read_u32(bytes, 12)?;
+        let width = read_u32(bytes, 16)?;
+        let level_count = if flags & DDSD_MIPMAPCOUNT != 0 {
+            read_u32(bytes, 28)?.max(1)
+        } else {
+            1
+        };
+        let pixel_format_flags = read_u32(bytes, 80)?;
+        let four_cc = read_range(bytes, 84, 4)?;
+        let caps2 = read_u32(bytes, 112)?;
+        if caps2 & DDSCAPS2_VOLUME != 0 {
+            return Err(invalid("3D textures aren't supported"));
+        }
+        if pixel_format_flags & DDPF_FOURCC == 0 {
+            return Err(invalid(
+                "Only DDS files with a FourCC or DX10 header are supported",
+            ));
+        }
+        let (format, array_layer_count, data_offset) = if four_cc == b"DX10" {
+            let format = dxgi_format(read_u32(bytes, 128)?)?;
+            let misc_flags = read_u32(bytes, 136)?;
+            let array_size = read_u32(bytes, 140)?.max(1);
+            let face_count = if misc_flags & DDS_RESOURCE_MISC_TEXTURECUBE != 0 {
+                6
+            } else {
+                1
+            };
+            let array_layer_count = array_size
+                .checked_mul(face_count)
+                .ok_or_else(|| invalid("Too many array layers"))?;
+            (format, array_layer_count, 148)
+        } else {
+            let face_count = if caps2 & DDSCAPS2_CUBEMAP != 0 { 6 } else { 1 };
+            (four_cc_format(four_cc)?, face_count, 128)
+        };
+        validate_size(format, width, height, level_count)?;
+        // DDS stores the whole mip chain of every layer after each other
+        let mut levels = vec![Vec::new(); level_count as usize];
+        let mut offset = data_offset;
+        for _ in 0..array_layer_count {
+            for (level, data) in levels.iter_mut().enumerate() {
+                let length = level_byte_length(format, width, height, level as u32, 1)?;
+                data.extend_from_slice(read_range(bytes, offset, length)?);
+                // The range was read so the end can't overflow
+                offset += length;
+            }
+        }
+        Ok(CompressedImage {
+            format,
+            width,
+            height,
+            array_layer_count,
+            levels,
+        })
+    }
+}
+
+/// A 2D texture array loaded from a KTX2 or DDS file with the blocks uploaded as they're
+/// stored. All mip levels and array layers in the file are uploaded, the mipmap option is
+/// ignored since compressed formats can't be downsampled. Cube maps are loaded as arrays
+/// with six layers per cube. The first view covers every layer and is followed by a 2D view
+/// per layer. Loading BCn textures requires the TEXTURE_COMPRESSION_BC feature.
+///
+/// Only uncompressed and BCn formats can be loaded. The wgpu version used doesn't have
+/// TextureFormat variants or device features for ETC2, EAC and ASTC, so files using them
+/// fail with a CompressedTextureError instead of being decoded on the CPU.
+pub struct CompressedTexture;
+
+impl TextureShaderLayout for CompressedTexture {
+    fn get_layout_entries() -> &'static [wgpu::BindGroupLayoutEntry] {
+        static ENTRIES: OnceCell<[wgpu::BindGroupLayoutEntry; 2]> = OnceCell::new();
+        ENTRIES.get_or_init(|| {
+            [
+                wgpu::BindGroupLayoutEntry::new(
+                    0,
+                    wgpu::ShaderStage::VERTEX
+                        | wgpu::ShaderStage::FRAGMENT
+                        | wgpu::ShaderStage::COMPUTE,
+                    wgpu::BindingType::SampledTexture {
+                        dimension: wgpu::TextureViewDimension::D2Array,
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: false,
+                    },
+                ),
+                wgpu::BindGroupLayoutEntry::new(
+                    1,
+                    wgpu::ShaderStage::VERTEX
+                        | wgpu::ShaderStage::FRAGMENT
+                        | wgpu::ShaderStage::COMPUTE,
+                    wgpu::BindingType::Sampler { comparison: false },
+                ),
+            ]
+        })
+    }
+
+    fn get_layout(device: &wgpu::Device) -> &'static wgpu::BindGroupLayout {
+        static LAYOUT: OnceCell<wgpu::BindGroupLayout> = OnceCell::new();
+        LAYOUT.get_or_init(move || {
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                bindings: Self::get_layout_entries(),
+                label: Some("CompressedTextureBindGroupLayout"),
+            })
+        })
+    }
+}
+
+impl LoadableTexture for CompressedTexture {
+    fn load_texture_with_options(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl AsRef<Path>,
+        options: &TextureLoadOptions,
+    ) -> Result<TextureData<Self>, RenderError> {
+        let image = CompressedImage::open(path)?;
+        let format = match options.get_color_space() {
+            Some(color_space) => with_color_space(image.format, color_space),
+            None => image.format,
+        };
+        let required_feature = wgpu::Features::TEXTURE_COMPRESSION_BC;
+        if is_bc_format(format) && !device.features().contains(required_feature) {
+            return Err(RenderError::MissingTextureFeature {
+                format,
+                feature: required_feature,
+            });
+        }
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("CompressedTexture"),
+            size: wgpu::Extent3d {
+                width: image.width,
+                height: image.height,
+                depth: image.array_layer_count,
+            },
+            mip_level_count: image.levels.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        });
+
+        let (block_size, block_bytes) = block_info(format);
+        for (mip_level, data) in image.levels.iter().enumerate() {
+            let (width, height) =
+                physical_size(format, image.width, image.height, mip_level as u32);
+            let texture_copy_view = wgpu::TextureCopyView {
+                texture: &texture,
+                mip_level: mip_level as u32,
+                origin: wgpu::Origin3d::ZERO,
+            };
+            let texture_data_layout = wgpu::TextureDataLayout {
+                offset: 0,
+                bytes_per_row: width / block_size * block_bytes,
+                rows_per_image: height,
+            };
+            let size = wgpu::Extent3d {
+                width,
+                height,
+                depth: image.array_layer_count,
+            };
+            queue.write_texture(texture_copy_view, data, texture_data_layout, size);
+        }
+
+        let array_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("CompressedTextureArray"),
+            format,
+            dimension: wgpu::TextureViewDimension::D2Array,
+            aspect: wgpu::TextureAspect::All,
+            base_mip_level: 0,
+            level_count: image.levels.len() as u32,
+            base_array_layer: 0,
+            array_layer_count: image.array_layer_count,
+        });
+        let layer_views = (0..image.array_layer_count).map(|layer| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("CompressedTextureLayer"),
+                format,
+                dimension: wgpu::TextureViewDimension::D2,
+                aspect: wgpu::TextureAspect::All,
+                base_mip_level: 0,
+                level_count: image.levels.len() as u32,
+                base_array_layer: layer,
+                array_layer_count: 1,
+            })
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: Self::get_layout(device),
+            bindings: &[
+                wgpu::Binding {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&array_view),
+                },
+                wgpu::Binding {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: Some("CompressedTextureBindGroup"),
+        });
+        Ok(TextureData {
+            bind_group,
+            sampler,
+            views: std::iter::once(array_view).chain(layer_views).collect(),
+            texture,
+            _marker: PhantomData::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn push_u32(bytes: &mut Vec<u8>, value: u32) {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    #[test]
+    fn parse_containers() {
+        // 8x8 BC1 texture with two layers and two mip levels
+        let level_0 = vec![1u8; 4 * 8 * 2];
+        let level_1 = vec![2u8; 8 * 2];
+        let mut ktx2 = KTX2_IDENTIFIER.to_vec();
+        for value in &[131, 1, 8, 8, 0, 2, 1, 2, 0] {
+            push_u32(&mut ktx2, *value);
+        }
+        ktx2.resize(80, 0);
+        for (offset, length) in &[(144, level_0.len()), (128, level_1.len())] {
+            ktx2.extend_from_slice(&(*offset as u64).to_le_bytes());
+            ktx2.extend_from_slice(&(*length as u64).to_le_bytes());
+            ktx2.extend_from_slice(&(*length as u64).to_le_bytes());
+        }
+        ktx2.extend_from_slice(&level_1);
+        ktx2.extend_from_slice(&level_0);
+        let image = CompressedImage::from_ktx2(&ktx2).unwrap();
+        assert_eq!(image.format, wgpu::TextureFormat::Bc1RgbaUnorm);
+        assert_eq!((image.width, image.height), (8, 8));
+        assert_eq!(image.array_layer_count, 2);
+        assert_eq!(image.levels, vec![level_0.clone(), level_1.clone()]);
+
+        // The same texture as a DX10 DDS file which stores it layer by layer
+        let mut dds = DDS_MAGIC.to_vec();
+        for value in &[124, DDSD_MIPMAPCOUNT, 8, 8, 0, 0, 2] {
+            push_u32(&mut dds, *value);
+        }
+        dds.resize(80, 0);
+        push_u32(&mut dds, DDPF_FOURCC);
+        dds.extend_from_slice(b"DX10");
+        dds.resize(128, 0);
+        for value in &[71, 3, 0, 2, 0] {
+            push_u32(&mut dds, *value);
+        }
+        for _ in 0..2 {
+            dds.extend_from_slice(&level_0[..32]);
+            dds.extend_from_slice(&level_1[..8]);
+        }
+        let image = CompressedImage::from_dds(&dds).unwrap();
+        assert_eq!(image.format, wgpu::TextureFormat::Bc1RgbaUnorm);
+        assert_eq!(image.array_layer_count, 2);
+        assert_eq!(image.levels, vec![level_0, level_1]);
+
+        // A 2x2 level still takes up a whole block
+        assert_eq!(
+            level_byte_length(wgpu::TextureFormat::Bc3RgbaUnorm, 8, 8, 2, 1).unwrap(),
+            16
+        );
+
+        // Corrupt headers are rejected before anything is allocated
+        let mut corrupt = ktx2.clone();
+        corrupt[40..44].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(CompressedImage::from_ktx2(&corrupt).is_err());
+        let mut corrupt = ktx2.clone();
+        corrupt[32..36].copy_from_slice(&u32::MAX.to_le_bytes());
+        corrupt[36..40].copy_from_slice(&6u32.to_le_bytes());
+        assert!(CompressedImage::from_ktx2(&corrupt).is_err());
+        let mut corrupt = ktx2.clone();
+        corrupt[80..88].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(CompressedImage::from_ktx2(&corrupt).is_err());
+        let mut corrupt = dds.clone();
+        corrupt[28..32].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(CompressedImage::from_dds(&corrupt).is_err());
+        // BC textures must be made of whole blocks
+        let mut corrupt = dds.clone();
+        corrupt[16..20].copy_from_slice(&6u32.to_le_bytes());
+        assert!(CompressedImage::from_dds(&corrupt).is_err());
+        let mut astc = KTX2_IDENTIFIER.to_vec();
+        push_u32(&mut astc, 157);
+        assert!(CompressedImage::from_ktx2(&astc).is_err());
+    }
+}
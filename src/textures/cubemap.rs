@@ -0,0 +1,381 @@
+use super::{
+    create_layered_texture,
+    mipmaps::Downsample,
+    pixel_format::{HdrImage, SourceImage},
+    LoadableTexture, TextureData, TextureLoadOptions, TextureShaderLayout,
+};
+use crate::RenderError;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use once_cell::sync::OnceCell;
+use std::{f32::consts::PI, marker::PhantomData, path::Path};
+
+// How the faces are arranged in a single image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CubeMapLayout {
+    // 4x3 faces with +Y above and -Y below +Z
+    HorizontalCross,
+    // 3x4 faces with -Z upside down below -Y
+    VerticalCross,
+    // 6x1 faces in cube map order
+    HorizontalStrip,
+    // 1x6 faces in cube map order
+    VerticalStrip,
+    // 2:1 longitude/latitude projection of the whole sphere
+    Equirectangular,
+}
+
+impl CubeMapLayout {
+    // Detects the layout from the aspect ratio and returns it with the face size
+    fn detect(width: u32, height: u32) -> Result<(CubeMapLayout, u32), RenderError> {
+        // Widened so the ratios of huge images can't overflow
+        let (wide_width, wide_height) = (u64::from(width), u64::from(height));
+        let detected = if wide_width * 3 == wide_height * 4 {
+            Some((CubeMapLayout::HorizontalCross, width / 4))
+        } else if wide_width * 4 == wide_height * 3 {
+            Some((CubeMapLayout::VerticalCross, width / 3))
+        } else if wide_width == wide_height * 6 {
+            Some((CubeMapLayout::HorizontalStrip, height))
+        } else if wide_width * 6 == wide_height {
+            Some((CubeMapLayout::VerticalStrip, width))
+        } else if wide_width == wide_height * 2 {
+            Some((CubeMapLayout::Equirectangular, height / 2))
+        } else {
+            None
+        };
+        match detected {
+            Some((layout, face_size)) if face_size > 0 => Ok((layout, face_size)),
+            Some((layout, _)) => Err(RenderError::CubeMapLayoutError {
+                reason: format!(
+                    "A {}x{} image is too small for the {:?} layout",
+                    width, height, layout
+                ),
+            }),
+            None => Err(RenderError::CubeMapLayoutError {
+                reason: format!(
+                    "A {}x{} image isn't a cross, a strip or an equirectangular image",
+                    width, height
+                ),
+            }),
+        }
+    }
+
+    // The column and row of every face in units of the face size
+    fn face_cells(self) -> [(u32, u32); 6] {
+        match self {
+            CubeMapLayout::HorizontalCross => [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)],
+            CubeMapLayout::VerticalCross => [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (1, 3)],
+            CubeMapLayout::HorizontalStrip => [(0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (5, 0)],
+            CubeMapLayout::VerticalStrip => [(0, 0), (0, 1), (0, 2), (0, 3), (0, 4), (0, 5)],
+            CubeMapLayout::Equirectangular => {
+                unreachable!("Equirectangular images are projected onto the faces")
+            }
+        }
+    }
+}
+
+// Splits a single image into the six faces
+fn split_faces(image: &SourceImage) -> Result<Vec<SourceImage>, RenderError> {
+    let (width, height) = image.dimensions();
+    let (layout, face_size) = CubeMapLayout::detect(width, height)?;
+    if layout == CubeMapLayout::Equirectangular {
+        return Ok(equirectangular_to_faces(image, face_size));
+    }
+    let faces = layout
+        .face_cells()
+        .iter()
+        .enumerate()
+        .map(|(face, (column, row))| {
+            let image = image.crop(column * face_size, row * face_size, face_size, face_size);
+            if layout == CubeMapLayout::VerticalCross && face == 5 {
+                image.rotate180()
+            } else {
+                image
+            }
+        })
+        .collect();
+    Ok(faces)
+}
+
+// The direction from the center of the cube through the point (u, v) in [-1, 1]
+// on the face, v points down in the face images
+fn face_direction(face: usize, u: f32, v: f32) -> [f32; 3] {
+    match face {
+        0 => [1.0, -v, -u],
+        1 => [-1.0, -v, u],
+        2 => [u, 1.0, v],
+        3 => [u, -1.0, -v],
+        4 => [u, -v, 1.0],
+        _ => [-u, -v, -1.0],
+    }
+}
+
+// Bilinear sample at a pixel position, wraps around horizontally and clamps at the poles
+fn sample_bilinear(image: &HdrImage, x: f32, y: f32) -> Rgba<f32> {
+    let (width, height) = image.dimensions();
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let column = |offset: i64| (x0 as i64 + offset).rem_euclid(i64::from(width)) as u32;
+    let row = |offset: i64| (y0 as i64 + offset).max(0).min(i64::from(height) - 1) as u32;
+    let mut result = [0.0; 4];
+    for &(dx, weight_x) in &[(0, 1.0 - fx), (1, fx)] {
+        for &(dy, weight_y) in &[(0, 1.0 - fy), (1, fy)] {
+            let pixel = image.get_pixel(column(dx), row(dy));
+            for (value, sample) in result.iter_mut().zip(pixel.0.iter()) {
+                *value += sample * weight_x * weight_y;
+            }
+        }
+    }
+    Rgba(result)
+}
+
+fn project_face(equirectangular: &HdrImage, face: usize, face_size: u32) -> HdrImage {
+    let (width, height) = equirectangular.dimensions();
+    ImageBuffer::from_fn(face_size, face_size, |x, y| {
+        let u = 2.0 * (x as f32 + 0.5) / face_size as f32 - 1.0;
+        let v = 2.0 * (y as f32 + 0.5) / face_size as f32 - 1.0;
+        let [dx, dy, dz] = face_direction(face, u, v);
+        let length = (dx * dx + dy * dy + dz * dz).sqrt();
+        // -Z is in the center of the image and +Y at the top
+        let longitude = dx.atan2(-dz);
+        let latitude = (dy / length).acos();
+        sample_bilinear(
+            equirectangular,
+            (longitude / (2.0 * PI) + 0.5) * width as f32 - 0.5,
+            latitude / PI * height as f32 - 0.5,
+        )
+    })
+}
+
+// LDR images are converted to RGBA and filtered in their stored encoding
+fn equirectangular_to_faces(image: &SourceImage, face_size: u32) -> Vec<SourceImage> {
+    match image {
+        SourceImage::Hdr(image) => (0..6)
+            .map(|face| SourceImage::Hdr(project_face(image, face, face_size)))
+            .collect(),
+        SourceImage::Ldr(image) => {
+            let rgba = image.to_rgba();
+            let image: HdrImage = ImageBuffer::from_fn(rgba.width(), rgba.height(), |x, y| {
+                let pixel = rgba.get_pixel(x, y);
+                Rgba([
+                    f32::from(pixel[0]) / 255.0,
+                    f32::from(pixel[1]) / 255.0,
+                    f32::from(pixel[2]) / 255.0,
+                    f32::from(pixel[3]) / 255.0,
+                ])
+            });
+            (0..6)
+                .map(|face| {
+                    let face = project_face(&image, face, face_size);
+                    let face = ImageBuffer::from_fn(face_size, face_size, |x, y| {
+                        let pixel = face.get_pixel(x, y);
+                        let to_u8 = |value: f32| (value * 255.0).round().max(0.0).min(255.0) as u8;
+                        Rgba([
+                            to_u8(pixel[0]),
+                            to_u8(pixel[1]),
+                            to_u8(pixel[2]),
+                            to_u8(pixel[3]),
+                        ])
+                    });
+                    SourceImage::Ldr(DynamicImage::ImageRgba8(face))
+                })
+                .collect()
+        }
+    }
+}
+
+/// A cube map for skyboxes and environment maps. The faces are stored in the array layers
+/// in the order +X, -X, +Y, -Y, +Z, -Z. `load_texture` reads a horizontal or vertical cross,
+/// a horizontal or vertical strip or an equirectangular image (usually HDR) which is
+/// projected onto the faces, the layout is detected from the aspect ratio. The first view
+/// is the cube view and is followed by a 2D view per face.
+pub struct CubeMapTexture;
+
+impl CubeMapTexture {
+    /// Loads a cube map from six separate images of the same size ordered +X, -X, +Y, -Y, +Z, -Z
+    pub fn load_faces<P: AsRef<Path>>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        faces: &[P; 6],
+        options: &TextureLoadOptions,
+    ) -> Result<TextureData<Self>, RenderError> {
+        let faces = faces
+            .iter()
+            .map(SourceImage::open)
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::create_texture_data(device, queue, &faces, options)
+    }
+
+    fn create_texture_data(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        faces: &[SourceImage],
+        options: &TextureLoadOptions,
+    ) -> Result<TextureData<Self>, RenderError> {
+        let (width, height) = faces[0].dimensions();
+        if width != height {
+            return Err(RenderError::CubeMapLayoutError {
+                reason: format!("The faces must be square but they are {}x{}", width, height),
+            });
+        }
+        let (texture, format) =
+            create_layered_texture(device, queue, Some("CubeMapTexture"), faces, options)?;
+        let level_count = options.get_mipmaps().level_count(width, height);
+        let cube_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("CubeMapView"),
+            format,
+            dimension: wgpu::TextureViewDimension::Cube,
+            aspect: wgpu::TextureAspect::All,
+            base_mip_level: 0,
+            level_count,
+            base_array_layer: 0,
+            array_layer_count: 6,
+        });
+        let face_views = (0..6).map(|face| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("CubeMapFaceView"),
+                format,
+                dimension: wgpu::TextureViewDimension::D2,
+                aspect: wgpu::TextureAspect::All,
+                base_mip_level: 0,
+                level_count,
+                base_array_layer: face,
+                array_layer_count: 1,
+            })
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: Self::get_layout(device),
+            bindings: &[
+                wgpu::Binding {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&cube_view),
+                },
+                wgpu::Binding {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: Some("CubeMapBindGroup"),
+        });
+        Ok(TextureData {
+            bind_group,
+            sampler,
+            views: std::iter::once(cube_view).chain(face_views).collect(),
+            texture,
+            _marker: PhantomData::default(),
+        })
+    }
+}
+
+impl TextureShaderLayout for CubeMapTexture {
+    fn get_layout_entries() -> &'static [wgpu::BindGroupLayoutEntry] {
+        static ENTRIES: OnceCell<[wgpu::BindGroupLayoutEntry; 2]> = OnceCell::new();
+        ENTRIES.get_or_init(|| {
+            [
+                wgpu::BindGroupLayoutEntry::new(
+                    0,
+                    wgpu::ShaderStage::VERTEX
+                        | wgpu::ShaderStage::FRAGMENT
+                        | wgpu::ShaderStage::COMPUTE,
+                    wgpu::BindingType::SampledTexture {
+                        dimension: wgpu::TextureViewDimension::Cube,
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: false,
+                    },
+                ),
+                wgpu::BindGroupLayoutEntry::new(
+                    1,
+                    wgpu::ShaderStage::VERTEX
+                        | wgpu::ShaderStage::FRAGMENT
+                        | wgpu::ShaderStage::COMPUTE,
+                    wgpu::BindingType::Sampler { comparison: false },
+                ),
+            ]
+        })
+    }
+
+    fn get_layout(device: &wgpu::Device) -> &'static wgpu::BindGroupLayout {
+        static LAYOUT: OnceCell<wgpu::BindGroupLayout> = OnceCell::new();
+        LAYOUT.get_or_init(move || {
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                bindings: Self::get_layout_entries(),
+                label: Some("CubeMapBindGroupLayout"),
+            })
+        })
+    }
+}
+
+impl LoadableTexture for CubeMapTexture {
+    fn load_texture_with_options(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl AsRef<Path>,
+        options: &TextureLoadOptions,
+    ) -> Result<TextureData<Self>, RenderError> {
+        let faces = split_faces(&SourceImage::open(path)?)?;
+        Self::create_texture_data(device, queue, &faces, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use image::RgbaImage;
+
+    #[test]
+    fn split_cross_and_equirectangular() {
+        // Every face is a single pixel with the face index in the red channel
+        let cross = RgbaImage::from_fn(4, 3, |x, y| {
+            let face = CubeMapLayout::HorizontalCross
+                .face_cells()
+                .iter()
+                .position(|&cell| cell == (x, y))
+                .map_or(255, |face| face as u8);
+            Rgba([face, 0, 0, 255])
+        });
+        let faces = split_faces(&SourceImage::Ldr(DynamicImage::ImageRgba8(cross))).unwrap();
+        for (index, face) in faces.iter().enumerate() {
+            match face {
+                SourceImage::Ldr(face) => {
+                    assert_eq!(face.to_rgba().into_raw(), vec![index as u8, 0, 0, 255])
+                }
+                SourceImage::Hdr(_) => panic!("LDR faces are expected"),
+            }
+        }
+        assert!(CubeMapLayout::detect(100, 100).is_err());
+        assert!(CubeMapLayout::detect(0, 0).is_err());
+        assert!(CubeMapLayout::detect(2, 1).is_err());
+        assert_eq!(
+            CubeMapLayout::detect(u32::MAX - 3, (u32::MAX - 3) / 4 * 3).unwrap(),
+            (CubeMapLayout::HorizontalCross, (u32::MAX - 3) / 4)
+        );
+
+        // The top half of the sphere is bright and the bottom half is dark
+        let sphere = ImageBuffer::from_fn(64, 32, |_, y| {
+            let value = if y < 16 { 4.0 } else { 0.0 };
+            Rgba([value, value, value, 1.0])
+        });
+        let faces = split_faces(&SourceImage::Hdr(sphere)).unwrap();
+        assert_eq!(faces.len(), 6);
+        let brightness = |face: usize, x: u32, y: u32| match &faces[face] {
+            SourceImage::Hdr(face) => face.get_pixel(x, y)[0],
+            SourceImage::Ldr(_) => panic!("HDR faces are expected"),
+        };
+        assert_eq!(faces[0].dimensions(), (16, 16));
+        assert!((brightness(2, 8, 8) - 4.0).abs() < 1e-4);
+        assert!(brightness(3, 8, 8).abs() < 1e-4);
+        // The horizon is in the middle of the side faces
+        assert!((brightness(4, 8, 2) - 4.0).abs() < 1e-4);
+        assert!(brightness(4, 8, 13).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,517 @@
+use super::pixel_format::{linear_to_srgb, srgb_to_linear, ColorSpace, HdrImage};
+use crate::{FragmentShader, RenderError, VertexShader};
+use image::{
+    imageops::FilterType, DynamicImage, GenericImageView, ImageBuffer, Luma, LumaA, Pixel,
+    Primitive, Rgb, Rgba,
+};
+use num_traits::{cast, ToPrimitive, Zero};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// The filter used to downsample every mip level from the one above it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MipmapFilter {
+    /// Averages every 2x2 block of the level above, the fastest filter
+    Box,
+    /// Fast linear filter, good enough for most textures
+    Triangle,
+    /// Sharper but noticeably slower
+    Lanczos3,
+}
+
+/// How the mip levels below the full resolution image are created
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MipmapGeneration {
+    /// Only the full resolution level is created
+    None,
+    /// The levels are downsampled with the image crate before uploading, sRGB images are
+    /// filtered in linear space like on the GPU
+    Cpu(MipmapFilter),
+    /// The levels are rendered from the level above with a linear filter on the GPU,
+    /// requires the format to be renderable
+    Gpu,
+}
+
+impl Default for MipmapGeneration {
+    fn default() -> Self {
+        MipmapGeneration::Cpu(MipmapFilter::Triangle)
+    }
+}
+
+impl MipmapGeneration {
+    /// Number of mip levels the texture needs, a full chain down to 1x1 unless None
+    pub fn level_count(&self, width: u32, height: u32) -> u32 {
+        match self {
+            MipmapGeneration::None => 1,
+            _ => full_mip_level_count(width, height),
+        }
+    }
+}
+
+/// The number of levels in a full mip chain, the last level is 1x1
+pub fn full_mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+// Every level is at least one pixel wide and high
+fn level_size(size: u32, level: u32) -> u32 {
+    (size >> level).max(1)
+}
+
+// The image filter of the MipmapFilter, the box filter is implemented here instead
+fn filter_type(filter: MipmapFilter) -> Option<FilterType> {
+    match filter {
+        MipmapFilter::Box => None,
+        MipmapFilter::Triangle => Some(FilterType::Triangle),
+        MipmapFilter::Lanczos3 => Some(FilterType::Lanczos3),
+    }
+}
+
+// Every target pixel is the average of the 2x2 block at twice its position, the last
+// row or column is repeated when the image is only one pixel high or wide
+fn box_downsample<P: Pixel + 'static>(
+    image: &ImageBuffer<P, Vec<P::Subpixel>>,
+    width: u32,
+    height: u32,
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let (source_width, source_height) = image.dimensions();
+    // Integer samples are rounded instead of truncated
+    let is_integer = cast::<f32, P::Subpixel>(0.5).and_then(|half| half.to_f32()) != Some(0.5);
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let mut sums = [0.0f32; 4];
+        for &source_y in &[2 * y, 2 * y + 1] {
+            for &source_x in &[2 * x, 2 * x + 1] {
+                let pixel = image.get_pixel(
+                    source_x.min(source_width - 1),
+                    source_y.min(source_height - 1),
+                );
+                for (sum, channel) in sums.iter_mut().zip(pixel.channels()) {
+                    *sum += channel.to_f32().unwrap_or(0.0);
+                }
+            }
+        }
+        let channels = sums[..P::CHANNEL_COUNT as usize]
+            .iter()
+            .map(|sum| {
+                let average = if is_integer {
+                    (sum / 4.0).round()
+                } else {
+                    sum / 4.0
+                };
+                cast(average).unwrap_or_else(P::Subpixel::zero)
+            })
+            .collect::<Vec<_>>();
+        *P::from_slice(&channels)
+    })
+}
+
+// Images that can be downsampled into mip levels
+pub(crate) trait Downsample: Sized {
+    fn dimensions(&self) -> (u32, u32);
+    // Colour spaces other than linear are filtered in linear space
+    fn downsample(
+        &self,
+        width: u32,
+        height: u32,
+        filter: MipmapFilter,
+        color_space: ColorSpace,
+    ) -> Self;
+}
+
+fn decode_srgb<T: Primitive + Into<f32> + 'static>(
+    image: &ImageBuffer<Rgba<T>, Vec<T>>,
+    max: T,
+) -> HdrImage {
+    let normalize = |value: T| value.into() / max.into();
+    ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, a] = image.get_pixel(x, y).0;
+        Rgba([
+            srgb_to_linear(normalize(r)),
+            srgb_to_linear(normalize(g)),
+            srgb_to_linear(normalize(b)),
+            normalize(a),
+        ])
+    })
+}
+
+// Averaging sRGB encoded values darkens every level, the image is decoded to linear floats,
+// filtered like HDR images and encoded again which matches sampling an sRGB view on the GPU
+fn downsample_srgb(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    filter: MipmapFilter,
+) -> DynamicImage {
+    let linear = match image {
+        DynamicImage::ImageLuma16(_)
+        | DynamicImage::ImageLumaA16(_)
+        | DynamicImage::ImageRgb16(_)
+        | DynamicImage::ImageRgba16(_) => decode_srgb(&image.to_rgba16(), u16::MAX),
+        _ => decode_srgb(&image.to_rgba8(), u8::MAX),
+    };
+    let level = linear.downsample(width, height, filter, ColorSpace::Linear);
+    // The alpha isn't encoded
+    let encoded = |x: u32, y: u32| {
+        let [r, g, b, a] = level.get_pixel(x, y).0;
+        [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), a]
+    };
+    let quantize = |value: f32, max: u16| (value.clamp(0.0, 1.0) * f32::from(max)).round() as u16;
+    let to_u16 = |value: f32| quantize(value, u16::MAX);
+    let to_u8 = |value: f32| quantize(value, u16::from(u8::MAX)) as u8;
+    match image {
+        // 16-bit images keep their channels since they're converted when uploaded
+        DynamicImage::ImageLuma16(_) => {
+            DynamicImage::ImageLuma16(ImageBuffer::from_fn(width, height, |x, y| {
+                let [r, ..] = encoded(x, y);
+                Luma([to_u16(r)])
+            }))
+        }
+        DynamicImage::ImageLumaA16(_) => {
+            DynamicImage::ImageLumaA16(ImageBuffer::from_fn(width, height, |x, y| {
+                let [r, _, _, a] = encoded(x, y);
+                LumaA([to_u16(r), to_u16(a)])
+            }))
+        }
+        DynamicImage::ImageRgb16(_) => {
+            DynamicImage::ImageRgb16(ImageBuffer::from_fn(width, height, |x, y| {
+                let [r, g, b, _] = encoded(x, y);
+                Rgb([to_u16(r), to_u16(g), to_u16(b)])
+            }))
+        }
+        DynamicImage::ImageRgba16(_) => {
+            DynamicImage::ImageRgba16(ImageBuffer::from_fn(width, height, |x, y| {
+                let [r, g, b, a] = encoded(x, y);
+                Rgba([to_u16(r), to_u16(g), to_u16(b), to_u16(a)])
+            }))
+        }
+        // 8-bit sRGB images are always uploaded as RGBA
+        _ => DynamicImage::ImageRgba8(ImageBuffer::from_fn(width, height, |x, y| {
+            let [r, g, b, a] = encoded(x, y);
+            Rgba([to_u8(r), to_u8(g), to_u8(b), to_u8(a)])
+        })),
+    }
+}
+
+impl Downsample for DynamicImage {
+    fn dimensions(&self) -> (u32, u32) {
+        GenericImageView::dimensions(self)
+    }
+
+    fn downsample(
+        &self,
+        width: u32,
+        height: u32,
+        filter: MipmapFilter,
+        color_space: ColorSpace,
+    ) -> Self {
+        if color_space == ColorSpace::Srgb {
+            return downsample_srgb(self, width, height, filter);
+        }
+        if let Some(filter) = filter_type(filter) {
+            return self.resize_exact(width, height, filter);
+        }
+        match self {
+            DynamicImage::ImageLuma8(image) => {
+                DynamicImage::ImageLuma8(box_downsample(image, width, height))
+            }
+            DynamicImage::ImageLumaA8(image) => {
+                DynamicImage::ImageLumaA8(box_downsample(image, width, height))
+            }
+            DynamicImage::ImageRgb8(image) => {
+                DynamicImage::ImageRgb8(box_downsample(image, width, height))
+            }
+            DynamicImage::ImageRgba8(image) => {
+                DynamicImage::ImageRgba8(box_downsample(image, width, height))
+            }
+            DynamicImage::ImageBgr8(image) => {
+                DynamicImage::ImageBgr8(box_downsample(image, width, height))
+            }
+            DynamicImage::ImageBgra8(image) => {
+                DynamicImage::ImageBgra8(box_downsample(image, width, height))
+            }
+            DynamicImage::ImageLuma16(image) => {
+                DynamicImage::ImageLuma16(box_downsample(image, width, height))
+            }
+            DynamicImage::ImageLumaA16(image) => {
+                DynamicImage::ImageLumaA16(box_downsample(image, width, height))
+            }
+            DynamicImage::ImageRgb16(image) => {
+                DynamicImage::ImageRgb16(box_downsample(image, width, height))
+            }
+            DynamicImage::ImageRgba16(image) => {
+                DynamicImage::ImageRgba16(box_downsample(image, width, height))
+            }
+        }
+    }
+}
+
+impl Downsample for HdrImage {
+    fn dimensions(&self) -> (u32, u32) {
+        ImageBuffer::dimensions(self)
+    }
+
+    // HDR images are always linear
+    fn downsample(&self, width: u32, height: u32, filter: MipmapFilter, _: ColorSpace) -> Self {
+        match filter_type(filter) {
+            Some(filter) => image::imageops::resize(self, width, height, filter),
+            None => box_downsample(self, width, height),
+        }
+    }
+}
+
+// Returns every level below the full resolution image
+pub(crate) fn generate_on_cpu<I: Downsample>(
+    image: &I,
+    filter: MipmapFilter,
+    color_space: ColorSpace,
+    level_count: u32,
+) -> Vec<I> {
+    let (width, height) = image.dimensions();
+    let mut levels: Vec<I> = Vec::new();
+    for level in 1..level_count {
+        let previous = levels.last().unwrap_or(image);
+        let level = previous.downsample(
+            level_size(width, level),
+            level_size(height, level),
+            filter,
+            color_space,
+        );
+        levels.push(level);
+    }
+    levels
+}
+
+// Compiled from shaders/mipmap.vs and shaders/mipmap.fs so mip levels can be generated on
+// the GPU without shaderc, recompile them to SPIR-V after changing the sources
+const VERTEX_SHADER: &[u8] = include_bytes!("shaders/mipmap.vs.spv");
+const FRAGMENT_SHADER: &[u8] = include_bytes!("shaders/mipmap.fs.spv");
+
+/// Renders the mip levels of textures on the GPU. The generator belongs to the device it was
+/// created with and creates a pipeline the first time each texture format is used. Share it
+/// between loads with TextureLoadOptions::set_mipmap_generator to reuse the pipelines.
+pub struct MipmapGenerator {
+    vertex_shader: VertexShader,
+    fragment_shader: FragmentShader,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    pipelines: Mutex<HashMap<wgpu::TextureFormat, Arc<wgpu::RenderPipeline>>>,
+}
+
+impl std::fmt::Debug for MipmapGenerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MipmapGenerator").finish()
+    }
+}
+
+impl MipmapGenerator {
+    pub fn new(device: &wgpu::Device) -> Result<Self, RenderError> {
+        let vertex_shader = VertexShader::from_spirv_bytes(device, VERTEX_SHADER)?;
+        let fragment_shader = FragmentShader::from_spirv_bytes(device, FRAGMENT_SHADER)?;
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                wgpu::BindGroupLayoutEntry::new(
+                    0,
+                    wgpu::ShaderStage::FRAGMENT,
+                    wgpu::BindingType::SampledTexture {
+                        dimension: wgpu::TextureViewDimension::D2,
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: false,
+                    },
+                ),
+                wgpu::BindGroupLayoutEntry::new(
+                    1,
+                    wgpu::ShaderStage::FRAGMENT,
+                    wgpu::BindingType::Sampler { comparison: false },
+                ),
+            ],
+            label: Some("MipmapBindGroupLayout"),
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        Ok(MipmapGenerator {
+            vertex_shader,
+            fragment_shader,
+            bind_group_layout,
+            sampler,
+            pipelines: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn get_pipeline(
+        &self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> Arc<wgpu::RenderPipeline> {
+        let mut pipelines = self
+            .pipelines
+            .lock()
+            .expect("Mipmap pipeline cache lock poisoned");
+        pipelines
+            .entry(format)
+            .or_insert_with(|| {
+                let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    bind_group_layouts: &[&self.bind_group_layout],
+                });
+                Arc::new(
+                    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                        layout: &layout,
+                        vertex_stage: self.vertex_shader.get_descriptor(),
+                        fragment_stage: Some(self.fragment_shader.get_descriptor()),
+                        rasterization_state: None,
+                        primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+                        color_states: &[wgpu::ColorStateDescriptor {
+                            format,
+                            alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                            color_blend: wgpu::BlendDescriptor::REPLACE,
+                            write_mask: wgpu::ColorWrite::ALL,
+                        }],
+                        depth_stencil_state: None,
+                        vertex_state: wgpu::VertexStateDescriptor {
+                            index_format: wgpu::IndexFormat::Uint16,
+                            vertex_buffers: &[],
+                        },
+                        sample_count: 1,
+                        sample_mask: !0,
+                        alpha_to_coverage_enabled: false,
+                    }),
+                )
+            })
+            .clone()
+    }
+
+    /// Records render passes that fill every mip level of each array layer of the 2D texture
+    /// by downsampling the level above it. Level 0 must already contain the images and the
+    /// texture must have been created with the SAMPLED and OUTPUT_ATTACHMENT usages on the
+    /// device of the generator.
+    pub fn generate_mipmaps(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        format: wgpu::TextureFormat,
+        mip_level_count: u32,
+        array_layer_count: u32,
+    ) {
+        let pipeline = self.get_pipeline(device, format);
+        for array_layer in 0..array_layer_count {
+            let views = (0..mip_level_count)
+                .map(|level| {
+                    texture.create_view(&wgpu::TextureViewDescriptor {
+                        label: Some("MipmapLevel"),
+                        format,
+                        dimension: wgpu::TextureViewDimension::D2,
+                        aspect: wgpu::TextureAspect::All,
+                        base_mip_level: level,
+                        level_count: 1,
+                        base_array_layer: array_layer,
+                        array_layer_count: 1,
+                    })
+                })
+                .collect::<Vec<_>>();
+            for level in 1..mip_level_count as usize {
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &self.bind_group_layout,
+                    bindings: &[
+                        wgpu::Binding {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&views[level - 1]),
+                        },
+                        wgpu::Binding {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&self.sampler),
+                        },
+                    ],
+                    label: Some("MipmapBindGroup"),
+                });
+                let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                        attachment: &views[level],
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                pass.set_pipeline(&pipeline);
+                pass.set_bind_group(0, &bind_group, &[]);
+                pass.draw(0..3, 0..1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn cpu_mip_chain() {
+        assert_eq!(full_mip_level_count(1, 1), 1);
+        assert_eq!(full_mip_level_count(256, 256), 9);
+        assert_eq!(full_mip_level_count(300, 17), 9);
+        let image = DynamicImage::ImageRgba8(image::RgbaImage::new(300, 17));
+        let level_count = MipmapGeneration::default().level_count(300, 17);
+        let expected_sizes = vec![
+            (150, 8),
+            (75, 4),
+            (37, 2),
+            (18, 1),
+            (9, 1),
+            (4, 1),
+            (2, 1),
+            (1, 1),
+        ];
+        for &filter in &[MipmapFilter::Box, MipmapFilter::Triangle] {
+            let levels = generate_on_cpu(&image, filter, ColorSpace::Srgb, level_count);
+            let sizes = levels
+                .iter()
+                .map(Downsample::dimensions)
+                .collect::<Vec<_>>();
+            assert_eq!(sizes, expected_sizes);
+        }
+
+        // Rounded averages of every 2x2 block, the single row is repeated
+        let image = DynamicImage::ImageLuma8(
+            image::GrayImage::from_raw(4, 1, vec![0, 11, 20, 255]).unwrap(),
+        );
+        let levels = generate_on_cpu(&image, MipmapFilter::Box, ColorSpace::Linear, 3);
+        assert_eq!(levels[0].to_bytes(), vec![6, 138]);
+        assert_eq!(levels[1].to_bytes(), vec![72]);
+        let image: HdrImage =
+            ImageBuffer::from_raw(1, 2, vec![0.0, 1.0, 0.5, 2.0, 1.0, 0.0, 0.0, 0.0]).unwrap();
+        let levels = generate_on_cpu(&image, MipmapFilter::Box, ColorSpace::Linear, 2);
+        assert_eq!(levels[0].as_raw(), &vec![0.5, 0.5, 0.25, 1.0]);
+
+        // sRGB images are averaged in linear space, black and white become 188 instead of 128
+        let image = DynamicImage::ImageRgba8(
+            image::RgbaImage::from_raw(2, 1, vec![0, 0, 0, 255, 255, 255, 255, 0]).unwrap(),
+        );
+        let levels = generate_on_cpu(&image, MipmapFilter::Box, ColorSpace::Srgb, 2);
+        assert_eq!(levels[0].to_bytes(), vec![188, 188, 188, 128]);
+        let levels = generate_on_cpu(&image, MipmapFilter::Box, ColorSpace::Linear, 2);
+        assert_eq!(levels[0].to_bytes(), vec![128, 128, 128, 128]);
+        let image = DynamicImage::ImageLumaA16(
+            ImageBuffer::from_raw(2, 1, vec![0, u16::MAX, u16::MAX, u16::MAX]).unwrap(),
+        );
+        let levels = generate_on_cpu(&image, MipmapFilter::Box, ColorSpace::Srgb, 2);
+        match &levels[0] {
+            DynamicImage::ImageLumaA16(level) => {
+                assert_eq!(level.as_raw(), &vec![48_192, u16::MAX])
+            }
+            _ => panic!("The 16-bit channels weren't kept"),
+        }
+    }
+}
@@ -1,9 +1,23 @@
 use crate::RenderError;
-use std::{marker::PhantomData, path::Path};
+use std::{marker::PhantomData, path::Path, sync::Arc};
 
+pub mod compressed;
+pub mod cubemap;
+pub mod mipmaps;
+pub mod pixel_format;
 pub mod simpletexture;
+pub mod texturearray;
+pub mod volumetexture;
+
+use mipmaps::{generate_on_cpu, Downsample, MipmapGeneration, MipmapGenerator};
+use pixel_format::{texel_size, ColorSpace, SourceImage, UploadFormat};
 
 pub trait TextureShaderLayout: 'static {
+    // Used to validate the layout against the shaders when building a RenderNode.
+    // Layouts without entries aren't validated.
+    fn get_layout_entries() -> &'static [wgpu::BindGroupLayoutEntry] {
+        &[]
+    }
     fn get_layout(device: &wgpu::Device) -> &'static wgpu::BindGroupLayout;
 }
 
@@ -43,11 +57,271 @@ impl<T: TextureShaderLayout> TextureData<T> {
     }
 }
 
+/// Options used when loading a texture from a file
+#[derive(Debug, Clone, Default)]
+pub struct TextureLoadOptions {
+    mipmaps: MipmapGeneration,
+    mipmap_generator: Option<Arc<MipmapGenerator>>,
+    color_space: Option<ColorSpace>,
+    grayscale_formats: bool,
+}
+
+impl TextureLoadOptions {
+    pub fn new() -> Self {
+        TextureLoadOptions::default()
+    }
+
+    /// Defaults to a full mip chain generated on the CPU
+    pub fn set_mipmaps(mut self, mipmaps: MipmapGeneration) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    pub fn get_mipmaps(&self) -> MipmapGeneration {
+        self.mipmaps
+    }
+
+    /// The generator used for MipmapGeneration::Gpu, it must belong to the device the
+    /// texture is loaded with. A generator is created for every load if none is set.
+    pub fn set_mipmap_generator(mut self, generator: Arc<MipmapGenerator>) -> Self {
+        self.mipmap_generator = Some(generator);
+        self
+    }
+
+    pub fn get_mipmap_generator(&self) -> Option<&MipmapGenerator> {
+        self.mipmap_generator.as_deref()
+    }
+
+    /// Overrides the detected colour space, grayscale images are loaded as linear and
+    /// colour images as sRGB by default. Normal and roughness maps should be loaded as
+    /// linear. HDR images are always linear.
+    pub fn set_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = Some(color_space);
+        self
+    }
+
+    pub fn get_color_space(&self) -> Option<ColorSpace> {
+        self.color_space
+    }
+
+    /// Stores grayscale images in one or two channel formats instead of expanding them to
+    /// RGBA, disabled by default. The value is then sampled from .r and the alpha from .g so
+    /// shaders can't use .rgb or .a of these textures. 8-bit sRGB images are still expanded
+    /// since there are no sRGB formats with fewer channels.
+    pub fn set_grayscale_formats(mut self, enabled: bool) -> Self {
+        self.grayscale_formats = enabled;
+        self
+    }
+
+    pub fn get_grayscale_formats(&self) -> bool {
+        self.grayscale_formats
+    }
+}
+
+// Checks that every image has the same size and pixel format as the first one and returns
+// the format they're uploaded as
+pub(crate) fn validate_layers(
+    layers: &[SourceImage],
+    options: &TextureLoadOptions,
+) -> Result<UploadFormat, RenderError> {
+    let first_layer = layers
+        .first()
+        .ok_or_else(|| RenderError::InvalidLayerStack {
+            reason: "At least one layer is needed".to_string(),
+        })?;
+    let (width, height) = first_layer.dimensions();
+    let upload_format =
+        first_layer.texture_format(options.get_color_space(), options.get_grayscale_formats());
+    for (layer, image) in layers.iter().enumerate().skip(1) {
+        if image.dimensions() != (width, height) {
+            return Err(RenderError::TextureLayerMismatch {
+                layer,
+                reason: format!(
+                    "The layer is {:?} but {:?} is expected",
+                    image.dimensions(),
+                    (width, height)
+                ),
+            });
+        }
+        let layer_format = image
+            .texture_format(options.get_color_space(), options.get_grayscale_formats())
+            .format;
+        if layer_format != upload_format.format {
+            return Err(RenderError::TextureLayerMismatch {
+                layer,
+                reason: format!(
+                    "The layer has the format {:?} but {:?} is expected",
+                    layer_format, upload_format.format
+                ),
+            });
+        }
+    }
+    Ok(upload_format)
+}
+
+// Creates a 2D texture with an array layer per image and uploads the images with their
+// mip levels. Every image must have the same size and pixel format as the first one.
+pub(crate) fn create_layered_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    label: Option<&str>,
+    layers: &[SourceImage],
+    options: &TextureLoadOptions,
+) -> Result<(wgpu::Texture, wgpu::TextureFormat), RenderError> {
+    let upload_format = validate_layers(layers, options)?;
+    let format = upload_format.format;
+    let (width, height) = layers[0].dimensions();
+
+    let mipmaps = options.get_mipmaps();
+    let mip_level_count = mipmaps.level_count(width, height);
+    let usage = wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST;
+    let usage = match mipmaps {
+        // The mip levels are rendered into the texture
+        MipmapGeneration::Gpu => usage | wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+        _ => usage,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label,
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth: layers.len() as u32,
+        },
+        mip_level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage,
+    });
+
+    for (array_layer, image) in layers.iter().enumerate() {
+        let levels = match mipmaps {
+            MipmapGeneration::Cpu(filter) => {
+                generate_on_cpu(image, filter, upload_format.color_space, mip_level_count)
+            }
+            _ => Vec::new(),
+        };
+        for (mip_level, level) in std::iter::once(image).chain(levels.iter()).enumerate() {
+            upload_format.write_texture(
+                queue,
+                &texture,
+                level,
+                mip_level as u32,
+                array_layer as u32,
+            );
+        }
+    }
+
+    if mipmaps == MipmapGeneration::Gpu {
+        let owned_generator;
+        let generator = match options.get_mipmap_generator() {
+            Some(generator) => generator,
+            None => {
+                owned_generator = MipmapGenerator::new(device)?;
+                &owned_generator
+            }
+        };
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("MipmapEncoder"),
+        });
+        generator.generate_mipmaps(
+            device,
+            &mut encoder,
+            &texture,
+            format,
+            mip_level_count,
+            layers.len() as u32,
+        );
+        queue.submit(vec![encoder.finish()]);
+    }
+    Ok((texture, format))
+}
+
+// Splits an image into layers of equal height stacked from top to bottom. Every layer is
+// flipped like other loaded textures while the top layer stays the first one.
+pub(crate) fn split_stack(
+    image: &SourceImage,
+    layer_count: u32,
+) -> Result<Vec<SourceImage>, RenderError> {
+    let (width, height) = image.dimensions();
+    if layer_count == 0 || height % layer_count != 0 {
+        return Err(RenderError::InvalidLayerStack {
+            reason: format!(
+                "An image with the height {} can't be split into {} layers",
+                height, layer_count
+            ),
+        });
+    }
+    let layer_height = height / layer_count;
+    let image = image.flipv();
+    Ok((0..layer_count)
+        .rev()
+        .map(|layer| image.crop(0, layer * layer_height, width, layer_height))
+        .collect())
+}
+
+// Splits an image into square layers stacked from top to bottom
+pub(crate) fn split_square_stack(image: &SourceImage) -> Result<Vec<SourceImage>, RenderError> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height % width != 0 {
+        return Err(RenderError::InvalidLayerStack {
+            reason: format!(
+                "A {}x{} image can't be split into square layers",
+                width, height
+            ),
+        });
+    }
+    split_stack(image, height / width)
+}
+
+// The bytes per row of raw texels with the layers or slices stored one after another
+// and tightly packed rows
+pub(crate) fn raw_bytes_per_row(
+    size: wgpu::Extent3d,
+    format: wgpu::TextureFormat,
+    data: &[u8],
+) -> Result<u32, RenderError> {
+    let texel_size = texel_size(format).ok_or_else(|| RenderError::InvalidLayerStack {
+        reason: format!(
+            "{:?} has no texel size, compressed textures must be loaded from files",
+            format
+        ),
+    })?;
+    let bytes_per_row = size.width as usize * texel_size as usize;
+    let expected = [size.height, size.depth]
+        .iter()
+        .try_fold(bytes_per_row, |length, &factor| {
+            length.checked_mul(factor as usize)
+        });
+    if expected.filter(|&length| length != 0) != Some(data.len()) {
+        return Err(RenderError::InvalidLayerStack {
+            reason: format!(
+                "{} bytes don't match {} layers of {}x{} {:?} texels",
+                data.len(),
+                size.depth,
+                size.width,
+                size.height,
+                format
+            ),
+        });
+    }
+    Ok(bytes_per_row as u32)
+}
+
 pub trait LoadableTexture: Sized + TextureShaderLayout {
     fn load_texture(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         path: impl AsRef<Path>,
+    ) -> Result<TextureData<Self>, RenderError> {
+        Self::load_texture_with_options(device, queue, path, &TextureLoadOptions::default())
+    }
+
+    fn load_texture_with_options(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl AsRef<Path>,
+        options: &TextureLoadOptions,
     ) -> Result<TextureData<Self>, RenderError>;
 }
 
@@ -56,3 +330,64 @@ pub trait Texture: Sized {
     where
         Self: TextureShaderLayout;
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use image::{DynamicImage, GrayImage, RgbaImage};
+
+    #[test]
+    fn layer_stacks() {
+        let stack = SourceImage::Ldr(DynamicImage::ImageRgba8(RgbaImage::new(4, 12)));
+        let layers = split_stack(&stack, 3).unwrap();
+        assert_eq!(layers.len(), 3);
+        assert!(layers.iter().all(|layer| layer.dimensions() == (4, 4)));
+        assert!(split_stack(&stack, 5).is_err());
+        assert!(validate_layers(&layers, &TextureLoadOptions::default()).is_ok());
+
+        let mut mixed = split_stack(&stack, 2).unwrap();
+        mixed.push(SourceImage::Ldr(DynamicImage::ImageLuma8(GrayImage::new(
+            4, 6,
+        ))));
+        match validate_layers(&mixed, &TextureLoadOptions::default()) {
+            Err(RenderError::TextureLayerMismatch { layer: 2, .. }) => {}
+            result => panic!("Expected a format mismatch but got {:?}", result.err()),
+        }
+        assert!(validate_layers(&[], &TextureLoadOptions::default()).is_err());
+
+        let size = wgpu::Extent3d {
+            width: 4,
+            height: 2,
+            depth: 3,
+        };
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        assert_eq!(
+            raw_bytes_per_row(size, format, &[0; 4 * 2 * 3 * 4]).unwrap(),
+            16
+        );
+        assert!(raw_bytes_per_row(size, format, &[0; 30]).is_err());
+        // The data has to match the size of the format and not just be divisible by it
+        assert!(raw_bytes_per_row(size, format, &[0; 4 * 2 * 3 * 8]).is_err());
+        assert!(
+            raw_bytes_per_row(size, wgpu::TextureFormat::Rg32Float, &[0; 4 * 2 * 3 * 8]).is_ok()
+        );
+        assert!(raw_bytes_per_row(size, wgpu::TextureFormat::Bc1RgbaUnorm, &[0; 12]).is_err());
+    }
+
+    #[test]
+    fn stack_orientation() {
+        // Two 1x2 layers with a different value in every row
+        let stack = GrayImage::from_raw(1, 4, vec![1, 2, 3, 4]).unwrap();
+        let layers = split_stack(&SourceImage::Ldr(DynamicImage::ImageLuma8(stack)), 2).unwrap();
+        let rows: Vec<_> = layers
+            .iter()
+            .map(|layer| match layer {
+                SourceImage::Ldr(image) => image.as_luma8().unwrap().to_vec(),
+                SourceImage::Hdr(_) => unreachable!(),
+            })
+            .collect();
+        // The top layer comes first and is flipped like a SimpleTexture
+        assert_eq!(rows, vec![vec![2, 1], vec![4, 3]]);
+    }
+}
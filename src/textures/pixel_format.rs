@@ -0,0 +1,356 @@
+use super::mipmaps::{Downsample, MipmapFilter};
+use crate::RenderError;
+use half::f16;
+use image::{hdr::HdrDecoder, DynamicImage, GenericImageView, ImageBuffer, Rgba};
+use std::{fs::File, io::BufReader, path::Path};
+
+/// How the colour values of a texture are encoded. Colour textures are sRGB while
+/// data such as normal or roughness maps must be linear so they're sampled unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+}
+
+pub(crate) type HdrImage = ImageBuffer<Rgba<f32>, Vec<f32>>;
+
+// A decoded image in the precision it was stored in
+pub(crate) enum SourceImage {
+    Ldr(DynamicImage),
+    // Radiance HDR images, always linear
+    Hdr(HdrImage),
+}
+
+impl SourceImage {
+    /// Opens .hdr files as floating point images and every other format with image::open
+    pub(crate) fn open(path: impl AsRef<Path>) -> Result<SourceImage, RenderError> {
+        let is_hdr = path
+            .as_ref()
+            .extension()
+            .map_or(false, |extension| extension.eq_ignore_ascii_case("hdr"));
+        if !is_hdr {
+            return Ok(SourceImage::Ldr(image::open(path)?));
+        }
+        let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+        let metadata = decoder.metadata();
+        let pixels = decoder.read_image_hdr()?;
+        let image = ImageBuffer::from_fn(metadata.width, metadata.height, |x, y| {
+            let pixel = pixels[(y * metadata.width + x) as usize];
+            Rgba([pixel.0[0], pixel.0[1], pixel.0[2], 1.0])
+        });
+        Ok(SourceImage::Hdr(image))
+    }
+
+    pub(crate) fn flipv(&self) -> SourceImage {
+        match self {
+            SourceImage::Ldr(image) => SourceImage::Ldr(image.flipv()),
+            SourceImage::Hdr(image) => SourceImage::Hdr(image::imageops::flip_vertical(image)),
+        }
+    }
+
+    pub(crate) fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> SourceImage {
+        match self {
+            SourceImage::Ldr(image) => SourceImage::Ldr(image.crop_imm(x, y, width, height)),
+            SourceImage::Hdr(image) => {
+                SourceImage::Hdr(image::imageops::crop_imm(image, x, y, width, height).to_image())
+            }
+        }
+    }
+
+    pub(crate) fn rotate180(&self) -> SourceImage {
+        match self {
+            SourceImage::Ldr(image) => SourceImage::Ldr(image.rotate180()),
+            SourceImage::Hdr(image) => SourceImage::Hdr(image::imageops::rotate180(image)),
+        }
+    }
+
+    // Grayscale images are assumed to be data and colour images to be sRGB encoded
+    fn default_color_space(&self) -> ColorSpace {
+        match self {
+            SourceImage::Ldr(DynamicImage::ImageLuma8(_))
+            | SourceImage::Ldr(DynamicImage::ImageLumaA8(_))
+            | SourceImage::Ldr(DynamicImage::ImageLuma16(_))
+            | SourceImage::Ldr(DynamicImage::ImageLumaA16(_))
+            | SourceImage::Hdr(_) => ColorSpace::Linear,
+            _ => ColorSpace::Srgb,
+        }
+    }
+
+    /// Picks the texture format that keeps the precision of the image. Grayscale images are
+    /// expanded to RGBA unless `grayscale_formats` is set, even then sRGB images are expanded
+    /// since there are no sRGB formats with less than four channels. 16-bit images are stored
+    /// as half floats since there are no 16-bit normalized formats, sRGB values are decoded
+    /// when converted.
+    pub(crate) fn texture_format(
+        &self,
+        color_space: Option<ColorSpace>,
+        grayscale_formats: bool,
+    ) -> UploadFormat {
+        let color_space = color_space.unwrap_or_else(|| self.default_color_space());
+        let image = match self {
+            SourceImage::Ldr(image) => image,
+            SourceImage::Hdr(_) => {
+                return UploadFormat {
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    color_space: ColorSpace::Linear,
+                }
+            }
+        };
+        let format = match (image, color_space) {
+            (DynamicImage::ImageLuma8(_), ColorSpace::Linear) if grayscale_formats => {
+                wgpu::TextureFormat::R8Unorm
+            }
+            (DynamicImage::ImageLumaA8(_), ColorSpace::Linear) if grayscale_formats => {
+                wgpu::TextureFormat::Rg8Unorm
+            }
+            (DynamicImage::ImageLuma16(_), _) if grayscale_formats => wgpu::TextureFormat::R16Float,
+            (DynamicImage::ImageLumaA16(_), _) if grayscale_formats => {
+                wgpu::TextureFormat::Rg16Float
+            }
+            (DynamicImage::ImageLuma16(_), _)
+            | (DynamicImage::ImageLumaA16(_), _)
+            | (DynamicImage::ImageRgb16(_), _)
+            | (DynamicImage::ImageRgba16(_), _) => wgpu::TextureFormat::Rgba16Float,
+            (_, ColorSpace::Linear) => wgpu::TextureFormat::Rgba8Unorm,
+            (_, ColorSpace::Srgb) => wgpu::TextureFormat::Rgba8UnormSrgb,
+        };
+        UploadFormat {
+            format,
+            color_space,
+        }
+    }
+}
+
+impl Downsample for SourceImage {
+    fn dimensions(&self) -> (u32, u32) {
+        match self {
+            SourceImage::Ldr(image) => GenericImageView::dimensions(image),
+            SourceImage::Hdr(image) => image.dimensions(),
+        }
+    }
+
+    fn downsample(
+        &self,
+        width: u32,
+        height: u32,
+        filter: MipmapFilter,
+        color_space: ColorSpace,
+    ) -> Self {
+        match self {
+            SourceImage::Ldr(image) => {
+                SourceImage::Ldr(image.downsample(width, height, filter, color_space))
+            }
+            SourceImage::Hdr(image) => {
+                SourceImage::Hdr(image.downsample(width, height, filter, color_space))
+            }
+        }
+    }
+}
+
+// The bytes per texel of formats that can be written from texels, compressed and depth
+// formats have none
+pub(crate) fn texel_size(format: wgpu::TextureFormat) -> Option<u32> {
+    use wgpu::TextureFormat::*;
+    match format {
+        R8Unorm | R8Snorm | R8Uint | R8Sint => Some(1),
+        R16Uint | R16Sint | R16Float | Rg8Unorm | Rg8Snorm | Rg8Uint | Rg8Sint => Some(2),
+        R32Uint | R32Sint | R32Float | Rg16Uint | Rg16Sint | Rg16Float | Rgba8Unorm
+        | Rgba8UnormSrgb | Rgba8Snorm | Rgba8Uint | Rgba8Sint | Bgra8Unorm | Bgra8UnormSrgb
+        | Rgb10a2Unorm | Rg11b10Float => Some(4),
+        Rg32Uint | Rg32Sint | Rg32Float | Rgba16Uint | Rgba16Sint | Rgba16Float => Some(8),
+        Rgba32Uint | Rgba32Sint | Rgba32Float => Some(16),
+        _ => None,
+    }
+}
+
+// The format a SourceImage is uploaded as
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct UploadFormat {
+    pub(crate) format: wgpu::TextureFormat,
+    pub(crate) color_space: ColorSpace,
+}
+
+impl UploadFormat {
+    pub(crate) fn bytes_per_pixel(&self) -> u32 {
+        texel_size(self.format).expect("Images are uploaded as uncompressed colour formats")
+    }
+
+    /// Uploads the image to a mip level and array layer of the texture
+    pub(crate) fn write_texture(
+        &self,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+        image: &SourceImage,
+        mip_level: u32,
+        array_layer: u32,
+    ) {
+        let (width, height) = image.dimensions();
+        let texture_copy_view = wgpu::TextureCopyView {
+            texture,
+            mip_level,
+            origin: wgpu::Origin3d {
+                x: 0,
+                y: 0,
+                z: array_layer,
+            },
+        };
+        let texture_data_layout = wgpu::TextureDataLayout {
+            offset: 0,
+            bytes_per_row: self.bytes_per_pixel() * width,
+            rows_per_image: 0,
+        };
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        };
+        queue.write_texture(
+            texture_copy_view,
+            &self.texels(image),
+            texture_data_layout,
+            size,
+        );
+    }
+
+    fn channel_count(&self) -> usize {
+        match self.format {
+            wgpu::TextureFormat::R8Unorm | wgpu::TextureFormat::R16Float => 1,
+            wgpu::TextureFormat::Rg8Unorm | wgpu::TextureFormat::Rg16Float => 2,
+            _ => 4,
+        }
+    }
+
+    /// Converts the image to tightly packed texels of the format
+    pub(crate) fn texels(&self, image: &SourceImage) -> Vec<u8> {
+        let image = match image {
+            SourceImage::Ldr(image) => image,
+            SourceImage::Hdr(image) => return half_float_texels(image.as_raw(), 4, 4, None),
+        };
+        let transfer = match self.color_space {
+            ColorSpace::Srgb => Some(srgb_to_linear as fn(f32) -> f32),
+            ColorSpace::Linear => None,
+        };
+        let target_channels = self.channel_count();
+        // image converts grayscale to RGBA by replicating the value
+        match (self.format, image) {
+            (wgpu::TextureFormat::R8Unorm, DynamicImage::ImageLuma8(buffer)) => buffer.to_vec(),
+            (wgpu::TextureFormat::Rg8Unorm, DynamicImage::ImageLumaA8(buffer)) => buffer.to_vec(),
+            (_, DynamicImage::ImageLuma16(buffer)) => {
+                half_float_texels(&normalize(buffer), 1, target_channels, transfer)
+            }
+            (_, DynamicImage::ImageLumaA16(buffer)) => {
+                half_float_texels(&normalize(buffer), 2, target_channels, transfer)
+            }
+            (_, DynamicImage::ImageRgb16(buffer)) => {
+                half_float_texels(&normalize(buffer), 3, target_channels, transfer)
+            }
+            (_, DynamicImage::ImageRgba16(buffer)) => {
+                half_float_texels(&normalize(buffer), 4, target_channels, transfer)
+            }
+            _ => image.to_rgba().into_raw(),
+        }
+    }
+}
+
+fn normalize(samples: &[u16]) -> Vec<f32> {
+    samples
+        .iter()
+        .map(|&sample| f32::from(sample) / f32::from(u16::MAX))
+        .collect()
+}
+
+pub(crate) fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub(crate) fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// Expands every pixel to the target channel count the same way image does for 8-bit
+// images, missing alpha is opaque. The transfer function isn't applied to alpha.
+fn half_float_texels(
+    samples: &[f32],
+    channels: usize,
+    target_channels: usize,
+    transfer: Option<fn(f32) -> f32>,
+) -> Vec<u8> {
+    let transfer = transfer.unwrap_or(|value| value);
+    let has_alpha = channels == 2 || channels == 4;
+    let mut texels = Vec::with_capacity(samples.len() / channels * target_channels * 2);
+    for pixel in samples.chunks_exact(channels) {
+        let alpha = if has_alpha { pixel[channels - 1] } else { 1.0 };
+        let expanded = match (channels, target_channels) {
+            (1, 1) | (2, 1) => [transfer(pixel[0]), 0.0, 0.0, 0.0],
+            (1, 2) | (2, 2) => [transfer(pixel[0]), alpha, 0.0, 0.0],
+            (1, _) | (2, _) => {
+                let value = transfer(pixel[0]);
+                [value, value, value, alpha]
+            }
+            _ => [
+                transfer(pixel[0]),
+                transfer(pixel[1]),
+                transfer(pixel[2]),
+                alpha,
+            ],
+        };
+        for value in expanded[..target_channels].iter() {
+            // Values above the largest half float would become infinity
+            let value = value.min(f32::from(f16::MAX));
+            texels.extend_from_slice(&f16::from_f32(value).to_bits().to_le_bytes());
+        }
+    }
+    texels
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use image::{GrayAlphaImage, ImageBuffer, Luma, Rgb};
+
+    #[test]
+    fn formats_and_expansion() {
+        let gray_alpha = SourceImage::Ldr(DynamicImage::ImageLumaA8(
+            GrayAlphaImage::from_raw(1, 1, vec![200, 100]).unwrap(),
+        ));
+        // Grayscale is expanded to RGBA unless smaller formats are requested
+        let expanded = gray_alpha.texture_format(None, false);
+        assert_eq!(expanded.format, wgpu::TextureFormat::Rgba8Unorm);
+        assert_eq!(expanded.texels(&gray_alpha), vec![200, 200, 200, 100]);
+        let linear = gray_alpha.texture_format(None, true);
+        assert_eq!(linear.format, wgpu::TextureFormat::Rg8Unorm);
+        assert_eq!(linear.texels(&gray_alpha), vec![200, 100]);
+        let srgb = gray_alpha.texture_format(Some(ColorSpace::Srgb), true);
+        assert_eq!(srgb.format, wgpu::TextureFormat::Rgba8UnormSrgb);
+        assert_eq!(srgb.texels(&gray_alpha), vec![200, 200, 200, 100]);
+
+        let rgb16 = SourceImage::Ldr(DynamicImage::ImageRgb16(
+            ImageBuffer::<Rgb<u16>, _>::from_raw(1, 1, vec![u16::MAX, 0, u16::MAX]).unwrap(),
+        ));
+        let format = rgb16.texture_format(None, false);
+        assert_eq!(format.format, wgpu::TextureFormat::Rgba16Float);
+        let one = f16::from_f32(1.0).to_bits().to_le_bytes();
+        let zero = f16::from_f32(0.0).to_bits().to_le_bytes();
+        assert_eq!(format.texels(&rgb16), [one, zero, one, one].concat());
+
+        let gray16 = SourceImage::Ldr(DynamicImage::ImageLuma16(
+            ImageBuffer::<Luma<u16>, _>::from_raw(1, 1, vec![u16::MAX]).unwrap(),
+        ));
+        let format = gray16.texture_format(None, true);
+        assert_eq!(format.format, wgpu::TextureFormat::R16Float);
+        assert_eq!(format.bytes_per_pixel(), 2);
+        assert_eq!(format.texels(&gray16), one.to_vec());
+        let format = gray16.texture_format(None, false);
+        assert_eq!(format.format, wgpu::TextureFormat::Rgba16Float);
+        assert_eq!(format.texels(&gray16), [one, one, one, one].concat());
+    }
+}
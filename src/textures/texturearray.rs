@@ -0,0 +1,210 @@
+use super::{
+    create_layered_texture, mipmaps::Downsample, pixel_format::SourceImage, raw_bytes_per_row,
+    split_square_stack, split_stack, LoadableTexture, TextureData, TextureLoadOptions,
+    TextureShaderLayout,
+};
+use crate::RenderError;
+use once_cell::sync::OnceCell;
+use std::{marker::PhantomData, path::Path};
+
+/// A 2D texture array, e.g. for terrain splat layers. Every layer must have the same size and
+/// pixel format. `load_texture` splits a single image into square layers stacked from top to
+/// bottom. Images are flipped vertically like a SimpleTexture. The first view covers every
+/// layer and is followed by a 2D view per layer.
+pub struct TextureArray;
+
+impl TextureArray {
+    /// Loads every image into its own layer in the given order
+    pub fn load_layers<P: AsRef<Path>>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        paths: &[P],
+        options: &TextureLoadOptions,
+    ) -> Result<TextureData<Self>, RenderError> {
+        let layers = paths
+            .iter()
+            .map(|path| SourceImage::open(path).map(|image| image.flipv()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_layers(device, queue, &layers, options)
+    }
+
+    /// Loads an image containing `layer_count` layers of equal height stacked from top to bottom
+    pub fn load_stack(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl AsRef<Path>,
+        layer_count: u32,
+        options: &TextureLoadOptions,
+    ) -> Result<TextureData<Self>, RenderError> {
+        let layers = split_stack(&SourceImage::open(path)?, layer_count)?;
+        Self::from_layers(device, queue, &layers, options)
+    }
+
+    /// Creates the array from raw texels of the format with the layers stored one after another
+    /// and tightly packed rows. The depth of the size is the number of layers. Only the full
+    /// resolution mip level is created. The rows aren't flipped and compressed formats aren't
+    /// supported.
+    pub fn from_raw(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        size: wgpu::Extent3d,
+        format: wgpu::TextureFormat,
+        data: &[u8],
+    ) -> Result<TextureData<Self>, RenderError> {
+        let bytes_per_row = raw_bytes_per_row(size, format, data)?;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("TextureArray"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        });
+        let texture_copy_view = wgpu::TextureCopyView {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+        };
+        let texture_data_layout = wgpu::TextureDataLayout {
+            offset: 0,
+            bytes_per_row,
+            rows_per_image: size.height,
+        };
+        queue.write_texture(texture_copy_view, data, texture_data_layout, size);
+        Ok(Self::create_texture_data(
+            device, texture, format, size.depth, 1,
+        ))
+    }
+
+    fn from_layers(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layers: &[SourceImage],
+        options: &TextureLoadOptions,
+    ) -> Result<TextureData<Self>, RenderError> {
+        let (texture, format) =
+            create_layered_texture(device, queue, Some("TextureArray"), layers, options)?;
+        let (width, height) = layers[0].dimensions();
+        let mip_level_count = options.get_mipmaps().level_count(width, height);
+        Ok(Self::create_texture_data(
+            device,
+            texture,
+            format,
+            layers.len() as u32,
+            mip_level_count,
+        ))
+    }
+
+    fn create_texture_data(
+        device: &wgpu::Device,
+        texture: wgpu::Texture,
+        format: wgpu::TextureFormat,
+        layer_count: u32,
+        mip_level_count: u32,
+    ) -> TextureData<Self> {
+        let array_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("TextureArrayView"),
+            format,
+            dimension: wgpu::TextureViewDimension::D2Array,
+            aspect: wgpu::TextureAspect::All,
+            base_mip_level: 0,
+            level_count: mip_level_count,
+            base_array_layer: 0,
+            array_layer_count: layer_count,
+        });
+        let layer_views = (0..layer_count).map(|layer| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("TextureArrayLayerView"),
+                format,
+                dimension: wgpu::TextureViewDimension::D2,
+                aspect: wgpu::TextureAspect::All,
+                base_mip_level: 0,
+                level_count: mip_level_count,
+                base_array_layer: layer,
+                array_layer_count: 1,
+            })
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: Self::get_layout(device),
+            bindings: &[
+                wgpu::Binding {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&array_view),
+                },
+                wgpu::Binding {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: Some("TextureArrayBindGroup"),
+        });
+        TextureData {
+            bind_group,
+            sampler,
+            views: std::iter::once(array_view).chain(layer_views).collect(),
+            texture,
+            _marker: PhantomData::default(),
+        }
+    }
+}
+
+impl TextureShaderLayout for TextureArray {
+    fn get_layout_entries() -> &'static [wgpu::BindGroupLayoutEntry] {
+        static ENTRIES: OnceCell<[wgpu::BindGroupLayoutEntry; 2]> = OnceCell::new();
+        ENTRIES.get_or_init(|| {
+            [
+                wgpu::BindGroupLayoutEntry::new(
+                    0,
+                    wgpu::ShaderStage::VERTEX
+                        | wgpu::ShaderStage::FRAGMENT
+                        | wgpu::ShaderStage::COMPUTE,
+                    wgpu::BindingType::SampledTexture {
+                        dimension: wgpu::TextureViewDimension::D2Array,
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: false,
+                    },
+                ),
+                wgpu::BindGroupLayoutEntry::new(
+                    1,
+                    wgpu::ShaderStage::VERTEX
+                        | wgpu::ShaderStage::FRAGMENT
+                        | wgpu::ShaderStage::COMPUTE,
+                    wgpu::BindingType::Sampler { comparison: false },
+                ),
+            ]
+        })
+    }
+
+    fn get_layout(device: &wgpu::Device) -> &'static wgpu::BindGroupLayout {
+        static LAYOUT: OnceCell<wgpu::BindGroupLayout> = OnceCell::new();
+        LAYOUT.get_or_init(move || {
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                bindings: Self::get_layout_entries(),
+                label: Some("TextureArrayBindGroupLayout"),
+            })
+        })
+    }
+}
+
+impl LoadableTexture for TextureArray {
+    fn load_texture_with_options(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl AsRef<Path>,
+        options: &TextureLoadOptions,
+    ) -> Result<TextureData<Self>, RenderError> {
+        let layers = split_square_stack(&SourceImage::open(path)?)?;
+        Self::from_layers(device, queue, &layers, options)
+    }
+}
@@ -0,0 +1,207 @@
+use super::{
+    mipmaps::Downsample, pixel_format::SourceImage, raw_bytes_per_row, split_square_stack,
+    split_stack, validate_layers, LoadableTexture, TextureData, TextureLoadOptions,
+    TextureShaderLayout,
+};
+use crate::RenderError;
+use once_cell::sync::OnceCell;
+use std::{marker::PhantomData, path::Path};
+
+/// A 3D texture, e.g. for volumetric fog lookup tables. Every depth slice must have the same
+/// size and pixel format. `load_texture` splits a single image into square slices stacked from
+/// top to bottom. Images are flipped vertically like a SimpleTexture. Volume textures only
+/// have a single mip level and the mipmap option is ignored. A 3D texture can't be viewed as
+/// 2D slices so `views` only contains the 3D view.
+pub struct VolumeTexture;
+
+impl VolumeTexture {
+    /// Loads every image into its own depth slice in the given order
+    pub fn load_slices<P: AsRef<Path>>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        paths: &[P],
+        options: &TextureLoadOptions,
+    ) -> Result<TextureData<Self>, RenderError> {
+        let slices = paths
+            .iter()
+            .map(|path| SourceImage::open(path).map(|image| image.flipv()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_slices(device, queue, &slices, options)
+    }
+
+    /// Loads an image containing `depth` slices of equal height stacked from top to bottom
+    pub fn load_stack(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl AsRef<Path>,
+        depth: u32,
+        options: &TextureLoadOptions,
+    ) -> Result<TextureData<Self>, RenderError> {
+        let slices = split_stack(&SourceImage::open(path)?, depth)?;
+        Self::from_slices(device, queue, &slices, options)
+    }
+
+    /// Creates the volume from raw texels of the format with the slices stored one after
+    /// another and tightly packed rows. The rows aren't flipped and compressed formats aren't
+    /// supported.
+    pub fn from_raw(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        size: wgpu::Extent3d,
+        format: wgpu::TextureFormat,
+        data: &[u8],
+    ) -> Result<TextureData<Self>, RenderError> {
+        let bytes_per_row = raw_bytes_per_row(size, format, data)?;
+        let texture = Self::allocate(device, size, format);
+        let texture_copy_view = wgpu::TextureCopyView {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+        };
+        let texture_data_layout = wgpu::TextureDataLayout {
+            offset: 0,
+            bytes_per_row,
+            rows_per_image: size.height,
+        };
+        queue.write_texture(texture_copy_view, data, texture_data_layout, size);
+        Ok(Self::create_texture_data(device, texture, format))
+    }
+
+    fn from_slices(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        slices: &[SourceImage],
+        options: &TextureLoadOptions,
+    ) -> Result<TextureData<Self>, RenderError> {
+        let upload_format = validate_layers(slices, options)?;
+        let (width, height) = slices[0].dimensions();
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth: slices.len() as u32,
+        };
+        let texture = Self::allocate(device, size, upload_format.format);
+        for (slice, image) in slices.iter().enumerate() {
+            upload_format.write_texture(queue, &texture, image, 0, slice as u32);
+        }
+        Ok(Self::create_texture_data(
+            device,
+            texture,
+            upload_format.format,
+        ))
+    }
+
+    fn allocate(
+        device: &wgpu::Device,
+        size: wgpu::Extent3d,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("VolumeTexture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        })
+    }
+
+    fn create_texture_data(
+        device: &wgpu::Device,
+        texture: wgpu::Texture,
+        format: wgpu::TextureFormat,
+    ) -> TextureData<Self> {
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("VolumeTextureView"),
+            format,
+            dimension: wgpu::TextureViewDimension::D3,
+            aspect: wgpu::TextureAspect::All,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            array_layer_count: 1,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: Self::get_layout(device),
+            bindings: &[
+                wgpu::Binding {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::Binding {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: Some("VolumeTextureBindGroup"),
+        });
+        TextureData {
+            bind_group,
+            sampler,
+            views: vec![view],
+            texture,
+            _marker: PhantomData::default(),
+        }
+    }
+}
+
+impl TextureShaderLayout for VolumeTexture {
+    fn get_layout_entries() -> &'static [wgpu::BindGroupLayoutEntry] {
+        static ENTRIES: OnceCell<[wgpu::BindGroupLayoutEntry; 2]> = OnceCell::new();
+        ENTRIES.get_or_init(|| {
+            [
+                wgpu::BindGroupLayoutEntry::new(
+                    0,
+                    wgpu::ShaderStage::VERTEX
+                        | wgpu::ShaderStage::FRAGMENT
+                        | wgpu::ShaderStage::COMPUTE,
+                    wgpu::BindingType::SampledTexture {
+                        dimension: wgpu::TextureViewDimension::D3,
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: false,
+                    },
+                ),
+                wgpu::BindGroupLayoutEntry::new(
+                    1,
+                    wgpu::ShaderStage::VERTEX
+                        | wgpu::ShaderStage::FRAGMENT
+                        | wgpu::ShaderStage::COMPUTE,
+                    wgpu::BindingType::Sampler { comparison: false },
+                ),
+            ]
+        })
+    }
+
+    fn get_layout(device: &wgpu::Device) -> &'static wgpu::BindGroupLayout {
+        static LAYOUT: OnceCell<wgpu::BindGroupLayout> = OnceCell::new();
+        LAYOUT.get_or_init(move || {
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                bindings: Self::get_layout_entries(),
+                label: Some("VolumeTextureBindGroupLayout"),
+            })
+        })
+    }
+}
+
+impl LoadableTexture for VolumeTexture {
+    fn load_texture_with_options(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl AsRef<Path>,
+        options: &TextureLoadOptions,
+    ) -> Result<TextureData<Self>, RenderError> {
+        let slices = split_square_stack(&SourceImage::open(path)?)?;
+        Self::from_slices(device, queue, &slices, options)
+    }
+}
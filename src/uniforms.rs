@@ -1,16 +1,49 @@
 use crate::{GpuData, RenderError};
 use smallvec::SmallVec;
-use std::{any::TypeId, fmt::Display};
+use std::{
+    any::{type_name, TypeId},
+    fmt::Display,
+    sync::Arc,
+};
 
 const UNIFORM_STACK_LIMIT: usize = 5;
+
+enum BindingKind {
+    Uniform,
+    // Each slot is aligned to wgpu::BIND_BUFFER_ALIGNMENT so it
+    // can be selected with a dynamic offset
+    DynamicUniform,
+    Storage { read_only: bool },
+}
+
 struct BindingInfo {
     size: usize,
+    // Number of elements of the GpuData type the buffer can hold
+    count: usize,
     visibility: wgpu::ShaderStage,
+    kind: BindingKind,
+}
+
+struct BindingBuffer {
+    buffer: wgpu::Buffer,
+    capacity: usize,
+    // Distance in bytes between slots in dynamic bindings
+    dynamic_stride: Option<wgpu::BufferAddress>,
+}
+
+// wgpu validates dynamic offsets against BIND_BUFFER_ALIGNMENT, the device limits of this
+// wgpu version don't report a minimum uniform buffer offset alignment to use instead
+#[inline]
+fn dynamic_stride(size: usize) -> wgpu::BufferAddress {
+    let size = size as wgpu::BufferAddress;
+    size.div_ceil(wgpu::BIND_BUFFER_ALIGNMENT) * wgpu::BIND_BUFFER_ALIGNMENT
 }
+
 pub struct UniformBindGroup {
-    buffers: SmallVec<[(TypeId, wgpu::Buffer); UNIFORM_STACK_LIMIT]>,
+    buffers: SmallVec<[(TypeId, BindingBuffer); UNIFORM_STACK_LIMIT]>,
     bind_group: Option<wgpu::BindGroup>, //Very ugly
     bind_group_layout: wgpu::BindGroupLayout,
+    layout_entries: SmallVec<[wgpu::BindGroupLayoutEntry; UNIFORM_STACK_LIMIT]>,
     label: &'static str,
 }
 
@@ -33,6 +66,78 @@ impl UniformBindGroup {
         &self.bind_group_layout
     }
 
+    pub(crate) fn get_layout_entries(&self) -> &[wgpu::BindGroupLayoutEntry] {
+        &self.layout_entries
+    }
+
+    /// Translates one slot index per dynamic binding, in binding order,
+    /// into the dynamic offsets passed to set_bind_group.
+    pub(crate) fn get_dynamic_offsets(
+        &self,
+        slots: &[u32],
+    ) -> Result<SmallVec<[wgpu::DynamicOffset; UNIFORM_STACK_LIMIT]>, RenderError> {
+        let dynamic_bindings = self
+            .buffers
+            .iter()
+            .filter_map(|(_, binding)| binding.dynamic_stride.map(|stride| (stride, binding)));
+        let dynamic_binding_count = dynamic_bindings.clone().count();
+        if dynamic_binding_count != slots.len() {
+            return Err(RenderError::InvalidDynamicSlots {
+                reason: format!(
+                    "{} has {} dynamic bindings but {} slots were given",
+                    self,
+                    dynamic_binding_count,
+                    slots.len()
+                ),
+            });
+        }
+        dynamic_bindings
+            .zip(slots.iter())
+            .map(|((stride, binding), slot)| {
+                if *slot as usize >= binding.capacity {
+                    return Err(RenderError::InvalidDynamicSlots {
+                        reason: format!(
+                            "Slot {} is out of bounds for a dynamic binding with {} slots in {}",
+                            slot, binding.capacity, self
+                        ),
+                    });
+                }
+                Ok((stride * *slot as wgpu::BufferAddress) as wgpu::DynamicOffset)
+            })
+            .collect()
+    }
+
+    // The bind group selected by set_dynamic_slots of the node runners
+    pub(crate) fn find_dynamic_group<'a>(
+        shared_groups: &'a [Arc<UniformBindGroup>],
+        local_groups: &'a [UniformBindGroup],
+        uniform_index: usize,
+    ) -> Result<&'a UniformBindGroup, RenderError> {
+        shared_groups
+            .iter()
+            .map(|shared| shared.as_ref())
+            .chain(local_groups.iter())
+            .nth(uniform_index)
+            .ok_or_else(|| RenderError::InvalidDynamicSlots {
+                reason: format!(
+                    "There is no uniform bind group at index {}, the node has {}",
+                    uniform_index,
+                    shared_groups.len() + local_groups.len()
+                ),
+            })
+    }
+
+    // Offsets pointing to the first slot of every dynamic binding
+    pub(crate) fn get_default_dynamic_offsets(
+        &self,
+    ) -> SmallVec<[wgpu::DynamicOffset; UNIFORM_STACK_LIMIT]> {
+        self.buffers
+            .iter()
+            .filter(|(_, binding)| binding.dynamic_stride.is_some())
+            .map(|_| 0)
+            .collect()
+    }
+
     pub fn get_bind_group(&self) -> &wgpu::BindGroup {
         &self
             .bind_group
@@ -47,14 +152,14 @@ impl UniformBindGroup {
         encoder: &mut wgpu::CommandEncoder,
         data: &T,
     ) -> Result<(), RenderError> {
-        if let Some((_, buffer)) = self.buffers.iter().find(|(id, _)| id == &TypeId::of::<T>()) {
+        if let Some((_, binding)) = self.buffers.iter().find(|(id, _)| id == &TypeId::of::<T>()) {
             let staging_buffer =
                 device.create_buffer_with_data(data.as_raw_bytes(), wgpu::BufferUsage::COPY_SRC);
 
             encoder.copy_buffer_to_buffer(
                 &staging_buffer,
                 0,
-                &buffer,
+                &binding.buffer,
                 0,
                 std::mem::size_of::<T>() as wgpu::BufferAddress,
             );
@@ -63,6 +168,88 @@ impl UniformBindGroup {
             Err(RenderError::GpuDataTypeNotPresent)
         }
     }
+
+    /// Writes a slice of GpuData into the slots of a dynamic binding starting at `first_slot`.
+    pub fn update_dynamic_buffer_data<T: GpuData>(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        first_slot: usize,
+        data: &[T],
+    ) -> Result<(), RenderError> {
+        let (_, binding) = self
+            .buffers
+            .iter()
+            .find(|(id, _)| id == &TypeId::of::<T>())
+            .ok_or(RenderError::GpuDataTypeNotPresent)?;
+        let stride = binding
+            .dynamic_stride
+            .ok_or(RenderError::GpuDataBindingNotDynamic)?;
+        if first_slot + data.len() > binding.capacity {
+            return Err(RenderError::BufferCapacityExceeded {
+                capacity: binding.capacity,
+                required: first_slot + data.len(),
+            });
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+        let mut raw_bytes = vec![0; stride as usize * data.len()];
+        data.iter()
+            .map(GpuData::as_raw_bytes)
+            .zip(raw_bytes.chunks_mut(stride as usize))
+            .for_each(|(bytes, slot)| slot[..bytes.len()].copy_from_slice(bytes));
+        let staging_buffer =
+            device.create_buffer_with_data(&raw_bytes, wgpu::BufferUsage::COPY_SRC);
+        encoder.copy_buffer_to_buffer(
+            &staging_buffer,
+            0,
+            &binding.buffer,
+            first_slot as wgpu::BufferAddress * stride,
+            raw_bytes.len() as wgpu::BufferAddress,
+        );
+        Ok(())
+    }
+
+    /// Writes a slice of GpuData into a storage binding starting at the element `offset`.
+    pub fn update_buffer_array_data<T: GpuData>(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        offset: usize,
+        data: &[T],
+    ) -> Result<(), RenderError> {
+        let (_, binding) = self
+            .buffers
+            .iter()
+            .find(|(id, _)| id == &TypeId::of::<T>())
+            .ok_or(RenderError::GpuDataTypeNotPresent)?;
+        if offset + data.len() > binding.capacity {
+            return Err(RenderError::BufferCapacityExceeded {
+                capacity: binding.capacity,
+                required: offset + data.len(),
+            });
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+        let raw_bytes = data
+            .iter()
+            .map(GpuData::as_raw_bytes)
+            .flatten()
+            .copied()
+            .collect::<Vec<u8>>();
+        let staging_buffer =
+            device.create_buffer_with_data(&raw_bytes, wgpu::BufferUsage::COPY_SRC);
+        encoder.copy_buffer_to_buffer(
+            &staging_buffer,
+            0,
+            &binding.buffer,
+            (offset * std::mem::size_of::<T>()) as wgpu::BufferAddress,
+            raw_bytes.len() as wgpu::BufferAddress,
+        );
+        Ok(())
+    }
 }
 
 pub struct UniformBindGroupBuilder {
@@ -85,10 +272,7 @@ impl UniformBindGroupBuilder {
         }
     }
 
-    pub fn add_binding<T: GpuData>(
-        mut self,
-        visibility: wgpu::ShaderStage,
-    ) -> Result<Self, RenderError> {
+    fn push_binding<T: GpuData>(mut self, binding_info: BindingInfo) -> Result<Self, RenderError> {
         if self
             .builder_data
             .iter()
@@ -99,35 +283,134 @@ impl UniformBindGroupBuilder {
         if std::mem::size_of::<T>() == 0 {
             return Err(RenderError::ZeroSizedGpuData);
         }
+        self.builder_data.push((TypeId::of::<T>(), binding_info));
+        Ok(self)
+    }
+
+    pub fn add_binding<T: GpuData>(
+        self,
+        visibility: wgpu::ShaderStage,
+    ) -> Result<Self, RenderError> {
         let binding_info = BindingInfo {
             size: std::mem::size_of::<T>(),
+            count: 1,
             visibility,
+            kind: BindingKind::Uniform,
         };
-        self.builder_data.push((TypeId::of::<T>(), binding_info));
-        Ok(self)
+        self.push_binding::<T>(binding_info)
+    }
+
+    /// Uniform binding with `slots` instances of T where the
+    /// slot used is selected when the bind group is set in a runner.
+    pub fn add_dynamic_binding<T: GpuData>(
+        self,
+        visibility: wgpu::ShaderStage,
+        slots: usize,
+    ) -> Result<Self, RenderError> {
+        if slots == 0 {
+            return Err(RenderError::InvalidDynamicSlots {
+                reason: format!(
+                    "The dynamic binding of {} needs at least one slot",
+                    type_name::<T>()
+                ),
+            });
+        }
+        let binding_info = BindingInfo {
+            size: std::mem::size_of::<T>(),
+            count: slots,
+            visibility,
+            kind: BindingKind::DynamicUniform,
+        };
+        self.push_binding::<T>(binding_info)
+    }
+
+    pub fn add_storage_binding<T: GpuData>(
+        self,
+        visibility: wgpu::ShaderStage,
+        read_only: bool,
+    ) -> Result<Self, RenderError> {
+        let binding_info = BindingInfo {
+            size: std::mem::size_of::<T>(),
+            count: 1,
+            visibility,
+            kind: BindingKind::Storage { read_only },
+        };
+        self.push_binding::<T>(binding_info)
+    }
+
+    /// Storage binding meant for runtime sized arrays in the shader,
+    /// the buffer can hold `count` elements of T.
+    pub fn add_storage_array_binding<T: GpuData>(
+        self,
+        visibility: wgpu::ShaderStage,
+        read_only: bool,
+        count: usize,
+    ) -> Result<Self, RenderError> {
+        if count == 0 {
+            return Err(RenderError::EmptyStorageArray);
+        }
+        let binding_info = BindingInfo {
+            size: std::mem::size_of::<T>(),
+            count,
+            visibility,
+            kind: BindingKind::Storage { read_only },
+        };
+        self.push_binding::<T>(binding_info)
     }
 
     pub fn build(self, device: &wgpu::Device) -> UniformBindGroup {
         let mut layout_entries: SmallVec<[wgpu::BindGroupLayoutEntry; UNIFORM_STACK_LIMIT]> =
             SmallVec::default();
-        let mut buffers: SmallVec<[(TypeId, wgpu::Buffer); UNIFORM_STACK_LIMIT]> =
+        let mut buffers: SmallVec<[(TypeId, BindingBuffer); UNIFORM_STACK_LIMIT]> =
             SmallVec::default();
         for (i, (id, info)) in self.builder_data.iter().enumerate() {
+            let (usage, binding_type) = match info.kind {
+                BindingKind::Uniform => (
+                    wgpu::BufferUsage::UNIFORM,
+                    wgpu::BindingType::UniformBuffer {
+                        dynamic: false,
+                        min_binding_size: wgpu::BufferSize::new(info.size as u64),
+                    },
+                ),
+                BindingKind::DynamicUniform => (
+                    wgpu::BufferUsage::UNIFORM,
+                    wgpu::BindingType::UniformBuffer {
+                        dynamic: true,
+                        min_binding_size: wgpu::BufferSize::new(info.size as u64),
+                    },
+                ),
+                BindingKind::Storage { read_only } => (
+                    wgpu::BufferUsage::STORAGE,
+                    wgpu::BindingType::StorageBuffer {
+                        dynamic: false,
+                        min_binding_size: wgpu::BufferSize::new(info.size as u64),
+                        readonly: read_only,
+                    },
+                ),
+            };
+            let dynamic_stride = match info.kind {
+                BindingKind::DynamicUniform => Some(dynamic_stride(info.size)),
+                _ => None,
+            };
             let buffer = device.create_buffer(&wgpu::BufferDescriptor {
                 label: Some(&format!("{} Binding buffer: {}", i, self.label)),
-                size: info.size as u64,
+                size: dynamic_stride.unwrap_or(info.size as u64) * info.count as u64,
                 mapped_at_creation: false,
-                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+                usage: usage | wgpu::BufferUsage::COPY_DST,
             });
 
-            buffers.push((*id, buffer));
+            buffers.push((
+                *id,
+                BindingBuffer {
+                    buffer,
+                    capacity: info.count,
+                    dynamic_stride,
+                },
+            ));
             layout_entries.push(wgpu::BindGroupLayoutEntry::new(
                 i as u32,
                 info.visibility,
-                wgpu::BindingType::UniformBuffer {
-                    dynamic: false,
-                    min_binding_size: wgpu::BufferSize::new(info.size as u64),
-                },
+                binding_type,
             ))
         }
 
@@ -139,6 +422,7 @@ impl UniformBindGroupBuilder {
             buffers,
             bind_group: None,
             bind_group_layout: layout,
+            layout_entries,
             label: self.label,
         };
         {
@@ -146,11 +430,18 @@ impl UniformBindGroupBuilder {
             uniform_bind_group
                 .buffers
                 .iter()
+                .zip(self.builder_data.iter())
                 .enumerate()
-                .for_each(|(i, (_, buffer))| {
+                .for_each(|(i, ((_, binding), (_, info)))| {
+                    // Dynamic bindings only expose a single slot at the time
+                    let buffer_slice = if binding.dynamic_stride.is_some() {
+                        binding.buffer.slice(..info.size as wgpu::BufferAddress)
+                    } else {
+                        binding.buffer.slice(..)
+                    };
                     bindings.push(wgpu::Binding {
                         binding: i as u32,
-                        resource: wgpu::BindingResource::Buffer(buffer.slice(..)),
+                        resource: wgpu::BindingResource::Buffer(buffer_slice),
                     });
                 });
 
@@ -274,4 +565,63 @@ mod tests {
             .is_err());
         Ok(())
     }
+
+    #[test]
+    fn storage_construction() -> Result<(), RenderError> {
+        let (device, _) = create_test_env();
+        let group = UniformBindGroup::builder()
+            .add_storage_binding::<Data1>(wgpu::ShaderStage::COMPUTE, false)?
+            .add_storage_array_binding::<Data2>(wgpu::ShaderStage::VERTEX, true, 3)?
+            .build(&device);
+
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        assert!(group
+            .update_buffer_data(&device, &mut encoder, &Data1 { dummy: 1 })
+            .is_ok());
+        let array = [Data2 { dummy: 1 }, Data2 { dummy: 2 }];
+        assert!(group
+            .update_buffer_array_data(&device, &mut encoder, 1, &array)
+            .is_ok());
+        assert!(group
+            .update_buffer_array_data(&device, &mut encoder, 2, &array)
+            .is_err());
+        assert!(group
+            .update_buffer_array_data(&device, &mut encoder, 0, &[Data3 { dummy: 3 }])
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn dynamic_construction() -> Result<(), RenderError> {
+        let (device, _) = create_test_env();
+        let group = UniformBindGroup::builder()
+            .add_binding::<Data1>(wgpu::ShaderStage::VERTEX)?
+            .add_dynamic_binding::<Data2>(wgpu::ShaderStage::VERTEX, 4)?
+            .build(&device);
+
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        let data = [Data2 { dummy: 1 }, Data2 { dummy: 2 }];
+        assert!(group
+            .update_dynamic_buffer_data(&device, &mut encoder, 2, &data)
+            .is_ok());
+        assert!(group
+            .update_dynamic_buffer_data(&device, &mut encoder, 3, &data)
+            .is_err());
+        assert!(group
+            .update_dynamic_buffer_data(&device, &mut encoder, 0, &[Data1 { dummy: 1 }])
+            .is_err());
+        assert_eq!(group.get_default_dynamic_offsets().as_slice(), &[0]);
+        assert_eq!(
+            group.get_dynamic_offsets(&[3])?.as_slice(),
+            &[3 * wgpu::BIND_BUFFER_ALIGNMENT as wgpu::DynamicOffset]
+        );
+        assert!(group.get_dynamic_offsets(&[4]).is_err());
+        assert!(group.get_dynamic_offsets(&[0, 0]).is_err());
+        assert!(UniformBindGroup::find_dynamic_group(&[], &[group], 1).is_err());
+        Ok(())
+    }
 }